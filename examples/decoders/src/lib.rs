@@ -37,7 +37,8 @@ mod widget {
                     width: data.y_dim.0 as u32,
                     height: data.y_dim.1 as u32,
                 },
-                data: bytes,
+                data: bytes.into(),
+                ..Default::default()
             }
         }
     }
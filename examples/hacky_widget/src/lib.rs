@@ -3,19 +3,22 @@ use std::time::Duration;
 use iced::advanced::layout;
 use iced::advanced::mouse::Cursor;
 use iced::advanced::renderer;
+use iced::advanced::text::{self, Paragraph, Text};
 use iced::advanced::widget::tree::{self, Tree};
 use iced::advanced::{Clipboard, Layout, Shell, Widget};
 use iced::event::{self, Event};
 use iced::widget::Shader;
 use iced::window::RedrawRequest;
-use iced::{window, Size};
-use iced::{Element, Length, Rectangle};
+use iced::{alignment, font, window, Size};
+use iced::{Color, Element, Font, Length, Pixels, Point, Rectangle, Vector};
 use web_time::Instant;
 
-use vanilla_iced::{Format, Program, Yuv};
+use vanilla_iced::{Buffering, Format, Program, Yuv};
 
+mod subtitles;
 mod types;
 
+pub use subtitles::{Align, Cue, Error, Line, Position, Style, SubtitleTrack};
 pub use types::VideoStream;
 
 const PLAYBACK_RATE: f32 = 1.0;
@@ -25,6 +28,7 @@ pub struct Video<'a> {
     height: Length,
     frame_duration: Duration,
     content: Box<dyn VideoStream + 'a>,
+    subtitles: Option<SubtitleTrack>,
 }
 
 impl<'a> Video<'a> {
@@ -34,9 +38,18 @@ impl<'a> Video<'a> {
             height: Length::Fill,
             frame_duration: Duration::from_secs_f64(1.0 / content.frame_rate()),
             content: Box::new(content),
+            subtitles: None,
         }
     }
 
+    /// Shows the active cues of a [`SubtitleTrack`] over the video.
+    ///
+    /// The presentation timestamp of each frame is its index over the frame rate.
+    pub fn subtitles(mut self, subtitles: SubtitleTrack) -> Self {
+        self.subtitles = Some(subtitles);
+        self
+    }
+
     /// Sets the width of the [`Video`] boundaries.
     pub fn width(mut self, width: impl Into<Length>) -> Self {
         self.width = width.into();
@@ -52,7 +65,9 @@ impl<'a> Video<'a> {
 
 impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Video<'a>
 where
-    Renderer: iced::advanced::Renderer + iced_wgpu::primitive::pipeline::Renderer,
+    Renderer: iced::advanced::Renderer
+        + iced::advanced::text::Renderer<Font = Font>
+        + iced_wgpu::primitive::pipeline::Renderer,
 {
     fn tag(&self) -> tree::Tag {
         tree::Tag::of::<State>()
@@ -101,9 +116,11 @@ where
     ) -> event::Status {
         let state = tree.state.downcast_mut::<State>();
 
-        let mut progress_frame = |i| {
+        let frame_duration = self.frame_duration;
+        let mut progress_frame = |i: usize| {
             if let Some(frame) = self.content.next(i) {
                 state.program.update_frame(frame);
+                state.pts = frame_duration * i as u32;
             }
         };
 
@@ -151,14 +168,117 @@ where
             layout,
             cursor,
             viewport,
-        )
+        );
+
+        // custom primitives are drawn over the text of their layer
+        if let Some(subtitles) = &self.subtitles {
+            renderer.with_layer(layout.bounds(), |renderer| {
+                draw_cues(
+                    renderer,
+                    subtitles.active(state.pts),
+                    layout.bounds(),
+                    viewport,
+                );
+            });
+        }
+    }
+}
+
+/// Draws `cues` over the video within `bounds`, stacking those at the bottom upwards by
+/// their height once wrapped
+fn draw_cues<'a, Renderer>(
+    renderer: &mut Renderer,
+    cues: impl Iterator<Item = &'a Cue>,
+    bounds: Rectangle,
+    viewport: &Rectangle,
+) where
+    Renderer: iced::advanced::text::Renderer<Font = Font>,
+{
+    let size = (bounds.height / 20.0).max(12.0);
+    let line_height = text::LineHeight::default();
+    let line = line_height.to_absolute(Pixels(size)).0;
+    let margin = bounds.height / 20.0;
+    let mut bottom = bounds.y + bounds.height - margin;
+
+    for cue in cues {
+        let (x, horizontal_alignment) = match cue.position.align {
+            Align::Start => (bounds.x + margin, alignment::Horizontal::Left),
+            Align::Center => (bounds.center_x(), alignment::Horizontal::Center),
+            Align::End => (
+                bounds.x + bounds.width - margin,
+                alignment::Horizontal::Right,
+            ),
+        };
+
+        let text = Text {
+            content: &cue.text,
+            bounds: Size::new(bounds.width - 2.0 * margin, bounds.height),
+            size: Pixels(size),
+            line_height,
+            font: Font {
+                weight: if cue.style.bold {
+                    font::Weight::Bold
+                } else {
+                    font::Weight::Normal
+                },
+                style: if cue.style.italic {
+                    font::Style::Italic
+                } else {
+                    font::Style::Normal
+                },
+                ..Font::DEFAULT
+            },
+            horizontal_alignment,
+            vertical_alignment: alignment::Vertical::Top,
+            shaping: text::Shaping::Advanced,
+        };
+
+        let (y, vertical_alignment) = match cue.position.line {
+            Some(Line::Fraction(fraction)) => (
+                bounds.y + fraction * bounds.height,
+                alignment::Vertical::Top,
+            ),
+            Some(Line::Number(number)) if number >= 0 => {
+                (bounds.y + number as f32 * line, alignment::Vertical::Top)
+            }
+            Some(Line::Number(number)) => (
+                bounds.y + bounds.height + (number + 1) as f32 * line,
+                alignment::Vertical::Bottom,
+            ),
+            None => {
+                let y = bottom;
+                // lines wrap within the bounds of the text, so the cue may be taller
+                // than its lines
+                bottom -= Renderer::Paragraph::with_text(text).min_bounds().height;
+
+                (y, alignment::Vertical::Bottom)
+            }
+        };
+        let text = Text {
+            vertical_alignment,
+            ..text
+        };
+        let color = cue
+            .style
+            .color
+            .map_or(Color::WHITE, |[r, g, b]| Color::from_rgb8(r, g, b));
+
+        // a drop shadow keeps the text legible over bright frames
+        let position = Point::new(x, y);
+        let shadow = Vector::new(size / 16.0, size / 16.0);
+
+        renderer.fill_text(text, position + shadow, Color::BLACK, *viewport);
+        renderer.fill_text(text, position, color, *viewport);
     }
 }
 
 impl<'a, Message, Theme, Renderer> From<Video<'a>> for Element<'a, Message, Theme, Renderer>
 where
     Message: 'a,
-    Renderer: iced::advanced::Renderer + iced_wgpu::primitive::pipeline::Renderer + 'a,
+    Renderer: iced::advanced::Renderer
+        + iced::advanced::text::Renderer<Font = Font>
+        + iced_wgpu::primitive::pipeline::Renderer
+        + 'a,
 {
     fn from(video: Video<'a>) -> Self {
         Self::new(video)
@@ -169,6 +289,8 @@ struct State {
     program: Program,
     last_draw: Option<Instant>,
     first_draw: Option<Instant>,
+    /// Presentation timestamp of the displayed frame
+    pts: Duration,
 }
 
 impl State {
@@ -176,11 +298,14 @@ impl State {
         Self {
             program: Program::new(Yuv {
                 format,
-                data: vec![],
+                data: Default::default(),
                 dimensions,
-            }),
+                ..Default::default()
+            })
+            .buffering(Buffering::Double),
             last_draw: None,
             first_draw: None,
+            pts: Duration::ZERO,
         }
     }
 }
@@ -0,0 +1,444 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("io error: {0:?}")]
+    IoError(#[from] std::io::Error),
+    #[error("missing WEBVTT header")]
+    MissingHeader,
+    #[error("invalid cue timing: {0}")]
+    InvalidTiming(String),
+    #[error("unsupported subtitle file: {0}")]
+    UnsupportedFile(String),
+}
+
+/// Cues of timed text, e.g. parsed from an SRT or WebVTT file
+#[derive(Debug, Clone, Default)]
+pub struct SubtitleTrack {
+    /// Sorted by their start
+    cues: Arc<[Cue]>,
+}
+
+/// Text shown from `start` until `end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    /// Lines of the cue, without markup
+    pub text: String,
+    pub style: Style,
+    pub position: Position,
+}
+
+/// Styling applied to the text of a [`Cue`] by its markup
+///
+/// Styles apply to the whole cue, even where the markup only covers part of its text, e.g.
+/// `<b>Hi</b> there` is bold throughout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    /// Set with `<font color>` in SRT, or a color class like `<c.yellow>` in WebVTT
+    pub color: Option<[u8; 3]>,
+}
+
+/// Placement of a [`Cue`] over the video
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    /// Vertical position of the cue, or `None` for the bottom
+    pub line: Option<Line>,
+    pub align: Align,
+}
+
+/// Vertical position of a [`Cue`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line {
+    /// Top of the cue relative to the video, from 0.0 to 1.0
+    Fraction(f32),
+    /// Line of the video in lines of text, counted from the top starting at 0 when
+    /// positive, and from the bottom starting at -1 when negative
+    Number(i32),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    #[default]
+    Center,
+    End,
+}
+
+impl SubtitleTrack {
+    pub fn new(cues: impl IntoIterator<Item = Cue>) -> Self {
+        let mut cues: Vec<_> = cues.into_iter().collect();
+        cues.sort_by_key(|cue| cue.start);
+
+        Self { cues: cues.into() }
+    }
+
+    /// Reads a `.srt` or `.vtt` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("srt") => Self::from_srt(&std::fs::read_to_string(path)?),
+            Some("vtt") => Self::from_vtt(&std::fs::read_to_string(path)?),
+            _ => Err(Error::UnsupportedFile(path.display().to_string())),
+        }
+    }
+
+    /// Parses SubRip cues, with `<b>`, `<i>` and `<font color>` markup and `{\an}` positions
+    pub fn from_srt(source: &str) -> Result<Self, Error> {
+        let cues = blocks(source)
+            .filter_map(|lines| {
+                // the counter before the timing is optional in practice
+                let timing = lines.iter().position(|line| line.contains("-->"))?;
+
+                Some(cue(&lines[timing], &lines[timing + 1..], Format::Srt))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(cues))
+    }
+
+    /// Parses WebVTT cues, with `<b>`, `<i>` and color class markup and the `line` and
+    /// `align` cue settings
+    pub fn from_vtt(source: &str) -> Result<Self, Error> {
+        let source = source.trim_start_matches('\u{feff}');
+
+        if !source
+            .strip_prefix("WEBVTT")
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return Err(Error::MissingHeader);
+        }
+
+        let cues = blocks(source)
+            .skip(1)
+            .filter_map(|lines| {
+                // NOTE, STYLE and REGION blocks have no timing
+                let timing = lines.iter().position(|line| line.contains("-->"))?;
+
+                Some(cue(&lines[timing], &lines[timing + 1..], Format::Vtt))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(cues))
+    }
+
+    pub fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    /// Cues shown at the presentation timestamp `pts`, in the order they start
+    pub fn active(&self, pts: Duration) -> impl Iterator<Item = &Cue> {
+        let started = self.cues.partition_point(|cue| cue.start <= pts);
+
+        self.cues[..started].iter().filter(move |cue| pts < cue.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Srt,
+    Vtt,
+}
+
+/// Lines of the blocks of a file, which are separated by blank lines
+fn blocks(source: &str) -> impl Iterator<Item = Vec<String>> + '_ {
+    let mut lines = source
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| line.trim_end().to_owned())
+        .peekable();
+
+    std::iter::from_fn(move || {
+        while lines.next_if(String::is_empty).is_some() {}
+
+        let block: Vec<_> = std::iter::from_fn(|| lines.next_if(|line| !line.is_empty())).collect();
+
+        (!block.is_empty()).then_some(block)
+    })
+}
+
+fn cue(timing: &str, text: &[String], format: Format) -> Result<Cue, Error> {
+    let invalid = || Error::InvalidTiming(timing.to_owned());
+
+    let (start, rest) = timing.split_once("-->").ok_or_else(invalid)?;
+    let mut rest = rest.split_whitespace();
+    let end = rest.next().ok_or_else(invalid)?;
+
+    let start = timestamp(start.trim()).ok_or_else(invalid)?;
+    let end = timestamp(end).ok_or_else(invalid)?;
+
+    let mut position = Position::default();
+
+    if format == Format::Vtt {
+        for (name, value) in rest.filter_map(|setting| setting.split_once(':')) {
+            // alignments of the line, e.g. `line:0,start`, aren't supported
+            let value = value.split(',').next().unwrap_or_default();
+
+            match name {
+                "line" => position.line = line(value),
+                "align" => position.align = align(value).unwrap_or(position.align),
+                _ => {}
+            }
+        }
+    }
+
+    let (text, style) = markup(&text.join("\n"), format, &mut position);
+
+    Ok(Cue {
+        start,
+        end,
+        text,
+        style,
+        position,
+    })
+}
+
+/// `HH:MM:SS,mmm` in SRT, or `[HH:]MM:SS.mmm` in WebVTT
+fn timestamp(timestamp: &str) -> Option<Duration> {
+    let (time, millis) = timestamp.split_once([',', '.'])?;
+
+    let mut parts = time.rsplit(':');
+    let seconds: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let hours: u64 = parts.next().map_or(Some(0), |hours| hours.parse().ok())?;
+
+    if parts.next().is_some() || millis.len() != 3 {
+        return None;
+    }
+
+    Some(
+        Duration::from_secs(hours * 3600 + minutes * 60 + seconds)
+            + Duration::from_millis(millis.parse().ok()?),
+    )
+}
+
+/// Value of the `line` setting, a percentage or a line number
+fn line(value: &str) -> Option<Line> {
+    if let Some(percentage) = value.strip_suffix('%') {
+        return percentage
+            .parse::<f32>()
+            .ok()
+            .map(|percentage| Line::Fraction((percentage / 100.0).clamp(0.0, 1.0)));
+    }
+
+    value.parse().ok().map(Line::Number)
+}
+
+fn align(value: &str) -> Option<Align> {
+    match value {
+        "start" | "left" => Some(Align::Start),
+        "center" | "middle" => Some(Align::Center),
+        "end" | "right" => Some(Align::End),
+        _ => None,
+    }
+}
+
+/// Strips the markup from `text`, applying its styles to the whole cue
+///
+/// SRT cues may be placed with an ASS override like `{\an8}`, which sets `position`.
+fn markup(text: &str, format: Format, position: &mut Position) -> (String, Style) {
+    let mut style = Style::default();
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(['<', '{']) {
+        plain.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let close = if rest.starts_with('<') { '>' } else { '}' };
+        let Some(end) = rest.find(close) else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if close == '}' {
+            match tag
+                .strip_prefix("\\an")
+                .and_then(|an| an.parse::<u8>().ok())
+            {
+                Some(an @ 1..=9) if format == Format::Srt => {
+                    position.line = match an {
+                        1..=3 => None,
+                        4..=6 => Some(Line::Fraction(0.45)),
+                        _ => Some(Line::Fraction(0.0)),
+                    };
+                    position.align = match an % 3 {
+                        1 => Align::Start,
+                        2 => Align::Center,
+                        _ => Align::End,
+                    };
+                }
+                _ => plain.push_str(&format!("{{{tag}}}")),
+            }
+
+            continue;
+        }
+
+        let mut classes = tag.split(|c: char| c == '.' || c.is_whitespace());
+        match classes
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "b" => style.bold = true,
+            "i" => style.italic = true,
+            "font" => {
+                style.color = tag
+                    .split_once("color=")
+                    .map(|(_, color)| color.trim_matches(|c: char| c == '"' || c == '\''))
+                    .and_then(|color| self::color(color.split_whitespace().next()?))
+                    .or(style.color);
+            }
+            "c" => {
+                style.color = classes.find_map(self::color).or(style.color);
+            }
+            _ => {}
+        }
+    }
+
+    plain.push_str(rest);
+
+    let plain = match format {
+        Format::Srt => plain,
+        Format::Vtt => plain
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&nbsp;", "\u{a0}")
+            .replace("&lrm;", "\u{200e}")
+            .replace("&rlm;", "\u{200f}")
+            .replace("&amp;", "&"),
+    };
+
+    (plain, style)
+}
+
+/// `#rrggbb` or one of the colors of the WebVTT color classes
+fn color(color: &str) -> Option<[u8; 3]> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        let [_, r, g, b] = value.to_be_bytes();
+
+        return Some([r, g, b]);
+    }
+
+    match color.to_ascii_lowercase().as_str() {
+        "white" => Some([255, 255, 255]),
+        "lime" => Some([0, 255, 0]),
+        "cyan" => Some([0, 255, 255]),
+        "red" => Some([255, 0, 0]),
+        "yellow" => Some([255, 255, 0]),
+        "magenta" => Some([255, 0, 255]),
+        "blue" => Some([0, 0, 255]),
+        "black" => Some([0, 0, 0]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srt() {
+        let track = SubtitleTrack::from_srt(
+            "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i>\r\nworld\r\n\r\n\
+             2\r\n00:00:03,000 --> 00:00:04,000\r\n{\\an8}<font color=\"#ff8000\">Top</font>\r\n",
+        )
+        .expect("valid srt");
+
+        let [hello, top] = track.cues() else {
+            panic!("two cues");
+        };
+
+        assert_eq!(hello.start, Duration::from_millis(1000));
+        assert_eq!(hello.end, Duration::from_millis(2500));
+        assert_eq!(hello.text, "Hello\nworld");
+        assert!(hello.style.italic);
+
+        assert_eq!(top.text, "Top");
+        assert_eq!(top.style.color, Some([255, 128, 0]));
+        assert_eq!(top.position.line, Some(Line::Fraction(0.0)));
+        assert_eq!(top.position.align, Align::Center);
+    }
+
+    #[test]
+    fn vtt() {
+        let track = SubtitleTrack::from_vtt(
+            "WEBVTT - example\n\nNOTE a comment\n\nintro\n00:01.000 --> 00:02.000 line:10% align:start\n\
+             <v Roger><b>Hi</b> &amp; <c.yellow>bye</c>\n\n01:00:00.000 --> 01:00:01.000\nLater\n",
+        )
+        .expect("valid vtt");
+
+        let [hi, later] = track.cues() else {
+            panic!("two cues");
+        };
+
+        assert_eq!(hi.text, "Hi & bye");
+        assert!(hi.style.bold);
+        assert_eq!(hi.style.color, Some([255, 255, 0]));
+        assert_eq!(hi.position.line, Some(Line::Fraction(0.1)));
+        assert_eq!(hi.position.align, Align::Start);
+        assert_eq!(later.start, Duration::from_secs(3600));
+
+        assert!(matches!(
+            SubtitleTrack::from_vtt("00:01.000 --> 00:02.000\nNo header"),
+            Err(Error::MissingHeader)
+        ));
+    }
+
+    #[test]
+    fn vtt_line_numbers() {
+        let track = SubtitleTrack::from_vtt(
+            "WEBVTT\n\n00:01.000 --> 00:02.000 line:3\nThird\n\n\
+             00:02.000 --> 00:03.000 line:-2,end\nPenultimate\n",
+        )
+        .expect("valid vtt");
+
+        let [third, penultimate] = track.cues() else {
+            panic!("two cues");
+        };
+
+        assert_eq!(third.position.line, Some(Line::Number(3)));
+        assert_eq!(penultimate.position.line, Some(Line::Number(-2)));
+    }
+
+    #[test]
+    fn active_cues() {
+        let cue = |start, end| Cue {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            text: String::new(),
+            style: Style::default(),
+            position: Position::default(),
+        };
+        let track = SubtitleTrack::new([cue(5, 6), cue(0, 10), cue(2, 3)]);
+
+        let active = |pts| {
+            track
+                .active(Duration::from_secs(pts))
+                .map(|cue| cue.start.as_secs())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(active(2), [0, 2]);
+        assert_eq!(active(3), [0]);
+        assert_eq!(active(5), [0, 5]);
+        assert!(active(10).is_empty());
+    }
+}
@@ -4,9 +4,10 @@ use anyhow::{anyhow, Result};
 use gstreamer::prelude::*;
 use num_traits::cast::ToPrimitive;
 
-use vanilla_iced::{Format, Size, Yuv};
+use vanilla_iced::{Data, Format, Size, Yuv};
 
-const FORMAT: Format = Format::NV12;
+// NV12 frames would be copied to I420 by the program, so the pipeline converts instead
+const FORMAT: Format = Format::I420;
 
 fn gstreamer_format_code(format: Format) -> &'static str {
     match format {
@@ -16,15 +17,6 @@ fn gstreamer_format_code(format: Format) -> &'static str {
     }
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct Bytes(Arc<Vec<u8>>);
-
-impl AsRef<[u8]> for Bytes {
-    fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
-    }
-}
-
 pub struct Player {
     source: gstreamer::Bin,
     width: u32,
@@ -79,9 +71,10 @@ impl Player {
             gstreamer_app::AppSinkCallbacks::builder()
                 .new_sample(move |sink| {
                     let sample = sink.pull_sample().map_err(|_| gstreamer::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gstreamer::FlowError::Error)?;
+                    let buffer = sample.buffer_owned().ok_or(gstreamer::FlowError::Error)?;
+                    // the mapped buffer is shared with the program rather than copied
                     let map = buffer
-                        .map_readable()
+                        .into_mapped_buffer_readable()
                         .map_err(|_| gstreamer::FlowError::Error)?;
 
                     let pad = sink.static_pad("sink").ok_or(gstreamer::FlowError::Error)?;
@@ -89,8 +82,6 @@ impl Player {
                     let caps = pad.current_caps().ok_or(gstreamer::FlowError::Error)?;
                     let _s = caps.structure(0).ok_or(gstreamer::FlowError::Error)?;
 
-                    let data = map.as_slice();
-
                     *frame_ref.lock().map_err(|_| gstreamer::FlowError::Error)? = Some(Yuv {
                         format: FORMAT,
                         // TODO: get this from gstreamer
@@ -98,7 +89,8 @@ impl Player {
                             width: 1280,
                             height: 720,
                         },
-                        data: data.to_vec(),
+                        data: Data::new(map),
+                        ..Default::default()
                     });
 
                     Ok(gstreamer::FlowSuccess::Ok)
@@ -26,7 +26,8 @@ impl From<MyYuv> for Yuv {
                 width: data.y_dim.0 as u32,
                 height: data.y_dim.1 as u32,
             },
-            data: bytes,
+            data: bytes.into(),
+            ..Default::default()
         }
     }
 }
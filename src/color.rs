@@ -0,0 +1,332 @@
+use glam::{Mat3, Vec3};
+
+/// Transfer characteristics of the RGB values encoded by a frame
+///
+/// Variants follow `transfer_characteristics` from ITU-T H.273.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransferFunction {
+    /// SDR gamma shared by BT.601, BT.709 and BT.2020, displayed per BT.1886
+    #[default]
+    Bt709,
+    /// IEC 61966-2-1 sRGB
+    Srgb,
+    /// Linear light, where 1.0 is SDR reference white
+    Linear,
+    /// SMPTE ST 2084 perceptual quantizer (HDR10)
+    Pq,
+    /// ARIB STD-B67 hybrid log-gamma
+    Hlg,
+}
+
+impl TransferFunction {
+    /// Whether the transfer function encodes luminance above SDR reference white
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Self::Pq | Self::Hlg)
+    }
+}
+
+/// Matrix used to derive the YUV planes of a frame from its non-linear RGB
+///
+/// Variants follow `matrix_coefficients` from ITU-T H.273.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatrixCoefficients {
+    /// BT.601 (SMPTE 170M, BT.470 B/G)
+    #[default]
+    Bt601,
+    /// BT.709
+    Bt709,
+    /// BT.2020 / BT.2100 non-constant luminance
+    Bt2020,
+    /// YCgCo, with Cg in the U plane and Co in the V plane
+    YCgCo,
+    /// BT.2100 ICtCp, which requires the [`TransferFunction::Pq`] or
+    /// [`TransferFunction::Hlg`] transfer and [`ColorPrimaries::Bt2020`] primaries
+    ICtCp,
+}
+
+impl MatrixCoefficients {
+    /// Matrix converting normalized YUV, with chroma centered on zero, to non-linear RGB,
+    /// or to non-linear LMS for [`MatrixCoefficients::ICtCp`]
+    pub(crate) fn to_rgb(self, transfer: TransferFunction) -> Mat3 {
+        match self {
+            Self::Bt601 => ycbcr(0.299, 0.114),
+            Self::Bt709 => ycbcr(0.2126, 0.0722),
+            Self::Bt2020 => ycbcr(0.2627, 0.0593),
+            Self::YCgCo => Mat3::from_cols_array(&[
+                1.0, 1.0, 1.0, //
+                -1.0, 1.0, -1.0, //
+                1.0, 0.0, -1.0,
+            ]),
+            Self::ICtCp => {
+                let to_ictcp = if transfer == TransferFunction::Hlg {
+                    Mat3::from_cols_array(&[
+                        2048.0, 3625.0, 9500.0, //
+                        2048.0, -7465.0, -9212.0, //
+                        0.0, 3840.0, -288.0,
+                    ])
+                } else {
+                    Mat3::from_cols_array(&[
+                        2048.0, 6610.0, 17933.0, //
+                        2048.0, -13613.0, -17390.0, //
+                        0.0, 7003.0, -543.0,
+                    ])
+                };
+
+                (to_ictcp * (1.0 / 4096.0)).inverse()
+            }
+        }
+    }
+}
+
+/// BT.2100 LMS to BT.2020 RGB, for frames encoded as ICtCp
+pub(crate) const LMS_TO_RGB: Mat3 = Mat3::from_cols_array(&[
+    3.436607, -0.791330, -0.025950, //
+    -2.506452, 1.9836, -0.098914, //
+    0.069845, -0.192271, 1.124864,
+]);
+
+/// Inverse of the Y'CbCr matrix derived from the luma coefficients of red and blue
+fn ycbcr(kr: f32, kb: f32) -> Mat3 {
+    let kg = 1.0 - kr - kb;
+
+    Mat3::from_cols_array(&[
+        1.0,
+        1.0,
+        1.0,
+        0.0,
+        -2.0 * kb * (1.0 - kb) / kg,
+        2.0 * (1.0 - kb),
+        2.0 * (1.0 - kr),
+        -2.0 * kr * (1.0 - kr) / kg,
+        0.0,
+    ])
+}
+
+/// Range of the code values of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorRange {
+    /// Luma from 16 to 235 and chroma from 16 to 240 (studio swing)
+    #[default]
+    Limited,
+    /// All code values from 0 to 255 (JPEG)
+    Full,
+}
+
+impl ColorRange {
+    /// Offset subtracted from the normalized samples of each plane before they are scaled
+    /// by [`ColorRange::scale`], moving black and neutral chroma to zero
+    pub(crate) fn offset(self) -> Vec3 {
+        match self {
+            Self::Limited => Vec3::new(16.0, 128.0, 128.0) / 255.0,
+            Self::Full => Vec3::new(0.0, 128.0, 128.0) / 255.0,
+        }
+    }
+
+    /// Scale of each plane to luma from 0.0 to 1.0 and chroma from -0.5 to 0.5
+    pub(crate) fn scale(self) -> Vec3 {
+        match self {
+            Self::Limited => Vec3::new(255.0 / 219.0, 255.0 / 224.0, 255.0 / 224.0),
+            Self::Full => Vec3::ONE,
+        }
+    }
+}
+
+/// Color primaries and white point of the RGB values encoded by a frame
+///
+/// Variants follow `colour_primaries` from ITU-T H.273.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorPrimaries {
+    /// BT.709 / sRGB
+    #[default]
+    Bt709,
+    /// BT.601 525 line systems (SMPTE 170M, NTSC)
+    Bt601,
+    /// BT.601 625 line systems (BT.470 B/G, PAL and SECAM)
+    Bt470Bg,
+    /// BT.2020 / BT.2100
+    Bt2020,
+    /// SMPTE EG 432-1 (Display P3, D65 white point)
+    DisplayP3,
+    /// SMPTE RP 431-2 (DCI-P3, DCI white point)
+    DciP3,
+}
+
+impl ColorPrimaries {
+    /// CIE 1931 xy chromaticities of the red, green and blue primaries and of the white point
+    fn chromaticities(&self) -> [(f32, f32); 4] {
+        const D65: (f32, f32) = (0.3127, 0.3290);
+
+        match self {
+            Self::Bt709 => [(0.640, 0.330), (0.300, 0.600), (0.150, 0.060), D65],
+            Self::Bt601 => [(0.630, 0.340), (0.310, 0.595), (0.155, 0.070), D65],
+            Self::Bt470Bg => [(0.640, 0.330), (0.290, 0.600), (0.150, 0.060), D65],
+            Self::Bt2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046), D65],
+            Self::DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060), D65],
+            Self::DciP3 => [
+                (0.680, 0.320),
+                (0.265, 0.690),
+                (0.150, 0.060),
+                (0.314, 0.351),
+            ],
+        }
+    }
+
+    /// Matrix converting linear RGB with these primaries to CIE XYZ
+    fn to_xyz(self) -> Mat3 {
+        let [red, green, blue, white] = self.chromaticities().map(xyz);
+        let primaries = Mat3::from_cols(red, green, blue);
+        let scale = primaries.inverse() * white;
+
+        primaries * Mat3::from_diagonal(scale)
+    }
+
+    fn white(self) -> Vec3 {
+        xyz(self.chromaticities()[3])
+    }
+
+    /// Matrix converting linear RGB with these primaries to linear RGB with `target` primaries,
+    /// adapting the white point with the Bradford transform
+    pub(crate) fn conversion(self, target: Self) -> Mat3 {
+        let bradford = Mat3::from_cols_array(&[
+            0.8951, -0.7502, 0.0389, //
+            0.2664, 1.7135, -0.0685, //
+            -0.1614, 0.0367, 1.0296,
+        ]);
+        let source_cone = bradford * self.white();
+        let target_cone = bradford * target.white();
+        let adaptation =
+            bradford.inverse() * Mat3::from_diagonal(target_cone / source_cone) * bradford;
+
+        target.to_xyz().inverse() * adaptation * self.to_xyz()
+    }
+}
+
+/// CIE XYZ with a luminance of 1 from xy chromaticities
+fn xyz((x, y): (f32, f32)) -> Vec3 {
+    Vec3::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a YUV sample with components from 0.0 to 1.0 to non-linear RGB (or LMS for
+    /// ICtCp), the same way the shader does before picture adjustments
+    fn yuv_to_rgb(
+        sample: Vec3,
+        matrix: MatrixCoefficients,
+        range: ColorRange,
+        transfer: TransferFunction,
+    ) -> Vec3 {
+        matrix.to_rgb(transfer) * ((sample - range.offset()) * range.scale())
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            actual.abs_diff_eq(expected, 0.01),
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn code_values(y: f32, u: f32, v: f32) -> Vec3 {
+        Vec3::new(y, u, v) / 255.0
+    }
+
+    #[test]
+    fn limited_range_black_and_white() {
+        for matrix in [
+            MatrixCoefficients::Bt601,
+            MatrixCoefficients::Bt709,
+            MatrixCoefficients::Bt2020,
+            MatrixCoefficients::YCgCo,
+        ] {
+            let convert =
+                |sample| yuv_to_rgb(sample, matrix, ColorRange::Limited, TransferFunction::Bt709);
+
+            assert_close(convert(code_values(16.0, 128.0, 128.0)), Vec3::ZERO);
+            assert_close(convert(code_values(235.0, 128.0, 128.0)), Vec3::ONE);
+        }
+    }
+
+    #[test]
+    fn ycbcr_primaries() {
+        let convert = |sample, matrix| {
+            yuv_to_rgb(sample, matrix, ColorRange::Limited, TransferFunction::Bt709)
+        };
+
+        // full red and green, and 75% gray
+        assert_close(
+            convert(code_values(81.0, 90.0, 240.0), MatrixCoefficients::Bt601),
+            Vec3::X,
+        );
+        assert_close(
+            convert(code_values(63.0, 102.0, 240.0), MatrixCoefficients::Bt709),
+            Vec3::X,
+        );
+        assert_close(
+            convert(code_values(74.0, 97.0, 240.0), MatrixCoefficients::Bt2020),
+            Vec3::X,
+        );
+        assert_close(
+            convert(code_values(145.0, 54.0, 34.0), MatrixCoefficients::Bt601),
+            Vec3::Y,
+        );
+        assert_close(
+            convert(code_values(180.0, 128.0, 128.0), MatrixCoefficients::Bt709),
+            Vec3::splat(0.75),
+        );
+    }
+
+    #[test]
+    fn ycgco() {
+        // Y = R/4 + G/2 + B/4, Cg = -R/4 + G/2 - B/4, Co = R/2 - B/2
+        let rgb = Vec3::new(0.5, 0.25, 0.75);
+        let sample = Vec3::new(0.4375, 128.0 / 255.0 - 0.1875, 128.0 / 255.0 - 0.125);
+
+        assert_close(
+            yuv_to_rgb(
+                sample,
+                MatrixCoefficients::YCgCo,
+                ColorRange::Full,
+                TransferFunction::Srgb,
+            ),
+            rgb,
+        );
+    }
+
+    #[test]
+    fn ictcp() {
+        // I = L'/2 + M'/2, with Ct and Cp from the BT.2100 PQ and HLG matrices
+        let lms = Vec3::new(0.6, 0.4, 0.3);
+        let neutral = 128.0 / 255.0;
+
+        let pq = Vec3::new(
+            0.5 * lms.x + 0.5 * lms.y,
+            (6610.0 * lms.x - 13613.0 * lms.y + 7003.0 * lms.z) / 4096.0 + neutral,
+            (17933.0 * lms.x - 17390.0 * lms.y - 543.0 * lms.z) / 4096.0 + neutral,
+        );
+        let hlg = Vec3::new(
+            0.5 * lms.x + 0.5 * lms.y,
+            (3625.0 * lms.x - 7465.0 * lms.y + 3840.0 * lms.z) / 4096.0 + neutral,
+            (9500.0 * lms.x - 9212.0 * lms.y - 288.0 * lms.z) / 4096.0 + neutral,
+        );
+
+        let convert = |sample, transfer| {
+            yuv_to_rgb(
+                sample,
+                MatrixCoefficients::ICtCp,
+                ColorRange::Full,
+                transfer,
+            )
+        };
+
+        assert_close(convert(pq, TransferFunction::Pq), lms);
+        assert_close(convert(hlg, TransferFunction::Hlg), lms);
+
+        // achromatic LMS has no Ct and Cp
+        assert_close(
+            convert(Vec3::new(0.5, neutral, neutral), TransferFunction::Pq),
+            Vec3::splat(0.5),
+        );
+    }
+}
@@ -0,0 +1,735 @@
+//! Conversion of frames to RGBA on the CPU
+//!
+//! Used where the shader can't run (e.g. on the tiny-skia renderer) and as a reference
+//! for the output of the shader. The color conversion matches the shader, but chroma is
+//! always upsampled bilinearly, interlaced frames are woven, [`Lut`](crate::Lut)s are
+//! not applied and blurred privacy regions approximate the Gaussian blur by box blurs.
+
+use glam::{Mat2, Mat3, Vec2, Vec3};
+use iced::widget::image;
+use iced::Point;
+use thiserror::Error;
+
+use crate::color::LMS_TO_RGB;
+use crate::program::{GamutMapping, Redaction, Regions, Settings, ToneMapping, REFERENCE_WHITE};
+use crate::{ColorPrimaries, Format, MatrixCoefficients, Renderable, Size, TransferFunction, Yuv};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("expected {expected} bytes of frame data, found {found}")]
+    DataLength { expected: usize, found: usize },
+    #[error("frame has no pixels")]
+    Empty,
+}
+
+/// Converts a frame to 8-bit sRGB RGBA, row by row without padding
+pub fn to_rgba(yuv: &Yuv) -> Result<Vec<u8>, Error> {
+    let mut settings = Settings::default();
+    settings.update(yuv);
+
+    Ok(convert(&Planes::try_from(yuv)?, &settings))
+}
+
+/// Converts a frame to an [`image::Handle`] which can be drawn by any renderer
+pub fn to_handle(yuv: &Yuv) -> Result<image::Handle, Error> {
+    Ok(image::Handle::from_pixels(
+        yuv.dimensions.width,
+        yuv.dimensions.height,
+        to_rgba(yuv)?,
+    ))
+}
+
+/// Converts an uploaded frame of a [`Program`](crate::Program) with its settings
+pub(crate) fn rgba(renderable: &Renderable, settings: &Settings) -> Result<Vec<u8>, Error> {
+    Ok(convert(&Planes::try_from(renderable)?, settings))
+}
+
+/// Converts an uploaded frame like [`rgba`], to an [`image::Handle`]
+pub(crate) fn handle(renderable: &Renderable, settings: &Settings) -> Result<image::Handle, Error> {
+    let Size { width, height } = renderable.dimensions();
+
+    Ok(image::Handle::from_pixels(
+        width,
+        height,
+        rgba(renderable, settings)?,
+    ))
+}
+
+/// Borrowed planes of a frame
+struct Planes<'a> {
+    y: &'a [u8],
+    u: &'a [u8],
+    v: &'a [u8],
+    dimensions: Size<u32>,
+    /// Chroma subsampling factor in both directions
+    factor: usize,
+    /// Row length of `u` and `v`, in bytes
+    chroma_width: usize,
+    chroma_height: usize,
+    /// Distance between horizontally adjacent samples of `u` and `v`
+    step: usize,
+}
+
+impl<'a> TryFrom<&'a Yuv> for Planes<'a> {
+    type Error = Error;
+
+    fn try_from(yuv: &'a Yuv) -> Result<Self, Error> {
+        Self::new(&yuv.data, yuv.dimensions, yuv.format)
+    }
+}
+
+impl<'a> TryFrom<&'a Renderable> for Planes<'a> {
+    type Error = Error;
+
+    fn try_from(renderable: &'a Renderable) -> Result<Self, Error> {
+        match renderable {
+            Renderable::I420 { data, dimensions } => Self::new(data, *dimensions, Format::I420),
+            Renderable::Y444 { data, dimensions } => Self::new(data, *dimensions, Format::Y444),
+        }
+    }
+}
+
+impl<'a> Planes<'a> {
+    /// Splits the `data` of a frame into its planes, which must all be complete
+    fn new(data: &'a [u8], dimensions: Size<u32>, format: Format) -> Result<Self, Error> {
+        let Size { width, height } = dimensions;
+        let (width, height) = (width as usize, height as usize);
+
+        if width == 0 || height == 0 {
+            return Err(Error::Empty);
+        }
+
+        let luma = width * height;
+        let factor = match format {
+            Format::I420 | Format::NV12 => 2,
+            Format::Y444 => 1,
+        };
+        let (chroma_width, chroma_height) = (width.div_ceil(factor), height.div_ceil(factor));
+        let chroma = chroma_width * chroma_height;
+
+        let expected = luma + 2 * chroma;
+        if data.len() < expected {
+            return Err(Error::DataLength {
+                expected,
+                found: data.len(),
+            });
+        }
+
+        let (y, chroma_data) = data[..expected].split_at(luma);
+
+        Ok(match format {
+            Format::I420 | Format::Y444 => Self {
+                y,
+                u: &chroma_data[..chroma],
+                v: &chroma_data[chroma..],
+                dimensions,
+                factor,
+                chroma_width,
+                chroma_height,
+                step: 1,
+            },
+            Format::NV12 => Self {
+                y,
+                u: chroma_data,
+                v: &chroma_data[1..],
+                dimensions,
+                factor,
+                chroma_width: chroma_width * 2,
+                chroma_height,
+                step: 2,
+            },
+        })
+    }
+}
+
+impl Planes<'_> {
+    /// Bilinearly interpolates a row of chroma between the rows of `vertical`, at the
+    /// columns of `horizontal`
+    fn chroma_row(&self, vertical: Tap, horizontal: &[Tap], u: &mut [f32], v: &mut [f32]) {
+        let top = vertical.first * self.chroma_width;
+        let bottom = vertical.second * self.chroma_width;
+
+        for (plane, out) in [(self.u, u), (self.v, v)] {
+            let (top, bottom) = (&plane[top..], &plane[bottom..]);
+
+            for (out, tap) in out.iter_mut().zip(horizontal) {
+                let (first, second) = (tap.first * self.step, tap.second * self.step);
+                let upper = lerp(top[first] as f32, top[second] as f32, tap.weight);
+                let lower = lerp(bottom[first] as f32, bottom[second] as f32, tap.weight);
+
+                *out = lerp(upper, lower, vertical.weight) / 255.0;
+            }
+        }
+    }
+}
+
+/// Neighbouring samples around a position, and the weight of the second
+#[derive(Clone, Copy)]
+struct Tap {
+    first: usize,
+    second: usize,
+    weight: f32,
+}
+
+impl Tap {
+    /// Taps at a position given in samples, clamped to the `len` samples
+    fn new(position: f32, len: usize) -> Self {
+        let position = position.clamp(0.0, (len - 1) as f32);
+        let first = position as usize;
+
+        Self {
+            first,
+            second: (first + 1).min(len - 1),
+            weight: position - first as f32,
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Converts row by row, with each step of the conversion applied to a whole row of
+/// samples at once
+fn convert(planes: &Planes, settings: &Settings) -> Vec<u8> {
+    let Size { width, height } = planes.dimensions;
+    let (width, height) = (width as usize, height as usize);
+    let conversion = Conversion::new(settings);
+
+    let factor = planes.factor as f32;
+    let (location_x, location_y) = if planes.factor > 1 {
+        settings.chroma_location.position()
+    } else {
+        (0.0, 0.0)
+    };
+
+    let columns = planes.chroma_width / planes.step;
+    let horizontal: Vec<Tap> = (0..width)
+        .map(|column| Tap::new((column as f32 - location_x) / factor, columns))
+        .collect();
+
+    let mut channels = [vec![0.0; width], vec![0.0; width], vec![0.0; width]];
+    let mut rgba = vec![u8::MAX; width * height * 4];
+
+    for (row, out) in rgba.chunks_exact_mut(width * 4).enumerate() {
+        let vertical = Tap::new((row as f32 - location_y) / factor, planes.chroma_height);
+        let [y, u, v] = &mut channels;
+
+        for (y, &luma) in y.iter_mut().zip(&planes.y[row * width..(row + 1) * width]) {
+            *y = luma as f32 / 255.0;
+        }
+        planes.chroma_row(vertical, &horizontal, u, v);
+
+        conversion.apply(&mut channels);
+
+        for (c, channel) in channels.iter().enumerate() {
+            for (pixel, &value) in out.chunks_exact_mut(4).zip(channel) {
+                pixel[c] = (value * 255.0 + 0.5) as u8;
+            }
+        }
+    }
+
+    redact(&mut rgba, width, height, &settings.privacy);
+
+    rgba
+}
+
+/// Hides the privacy regions of the converted frame, where pixelated regions show the
+/// pixel at the center of each block
+fn redact(rgba: &mut [u8], width: usize, height: usize, regions: &Regions) {
+    if regions.is_empty() {
+        return;
+    }
+
+    let source = rgba.to_vec();
+    // the whole frame is blurred once for each radius
+    let mut blurred: Vec<(f32, Vec<u8>)> = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let uv = Point::new(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            let Some(redaction) = regions.redaction(uv) else {
+                continue;
+            };
+
+            let color = match redaction {
+                Redaction::Solid(color) => color.into_rgba8(),
+                Redaction::Pixelate(size) => {
+                    let size = size as usize;
+                    let cx = (x / size * size + size / 2).min(width - 1);
+                    let cy = (y / size * size + size / 2).min(height - 1);
+                    let i = (cy * width + cx) * 4;
+
+                    [source[i], source[i + 1], source[i + 2], source[i + 3]]
+                }
+                Redaction::Blur(radius) => {
+                    let image = match blurred.iter().position(|(r, _)| *r == radius) {
+                        Some(i) => &blurred[i].1,
+                        None => {
+                            blurred.push((radius, blur(&source, width, height, radius)));
+                            &blurred[blurred.len() - 1].1
+                        }
+                    };
+                    let i = (y * width + x) * 4;
+
+                    [image[i], image[i + 1], image[i + 2], image[i + 3]]
+                }
+            };
+
+            let i = (y * width + x) * 4;
+            rgba[i..i + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+/// Approximates the Gaussian blur of the shader, with a standard deviation of half the
+/// `radius`, by three box blurs of the same variance
+fn blur(rgba: &[u8], width: usize, height: usize, radius: f32) -> Vec<u8> {
+    let half = ((radius * radius + 1.0).sqrt() / 2.0).round() as usize;
+
+    (0..3).fold(rgba.to_vec(), |image, _| {
+        let sums = SummedArea::new(&image, width, height);
+
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                sums.average(
+                    x.saturating_sub(half),
+                    y.saturating_sub(half),
+                    (x + half + 1).min(width),
+                    (y + half + 1).min(height),
+                )
+            })
+            .collect()
+    })
+}
+
+/// Sums of the channels of all pixels above and left of each position, so that the
+/// average of any rectangle takes four lookups
+struct SummedArea {
+    sums: Vec<[u32; 4]>,
+    stride: usize,
+}
+
+impl SummedArea {
+    fn new(rgba: &[u8], width: usize, height: usize) -> Self {
+        let stride = width + 1;
+        let mut sums = vec![[0; 4]; stride * (height + 1)];
+
+        for y in 0..height {
+            let mut row = [0; 4];
+
+            for x in 0..width {
+                let i = (y * width + x) * 4;
+
+                for c in 0..4 {
+                    row[c] += rgba[i + c] as u32;
+                    sums[(y + 1) * stride + x + 1][c] = sums[y * stride + x + 1][c] + row[c];
+                }
+            }
+        }
+
+        Self { sums, stride }
+    }
+
+    /// Average of the pixels from `left`, `top` up to `right`, `bottom`, exclusive
+    fn average(&self, left: usize, top: usize, right: usize, bottom: usize) -> [u8; 4] {
+        let at = |x: usize, y: usize| self.sums[y * self.stride + x];
+        let (a, b, c, d) = (
+            at(left, top),
+            at(right, top),
+            at(left, bottom),
+            at(right, bottom),
+        );
+        let count = ((right - left) * (bottom - top)) as u32;
+
+        std::array::from_fn(|i| ((d[i] + a[i] - b[i] - c[i] + count / 2) / count) as u8)
+    }
+}
+
+/// Applies `f` to each sample of each channel
+fn each(channels: &mut [Vec<f32>; 3], f: impl Fn(f32) -> f32) {
+    for channel in channels {
+        for c in channel {
+            *c = f(*c);
+        }
+    }
+}
+
+/// Applies `f` to the samples of all channels at each position
+fn each_rgb(channels: &mut [Vec<f32>; 3], f: impl Fn(Vec3) -> Vec3) {
+    let [r, g, b] = channels;
+
+    for ((r, g), b) in r.iter_mut().zip(g.iter_mut()).zip(b.iter_mut()) {
+        [*r, *g, *b] = f(Vec3::new(*r, *g, *b)).to_array();
+    }
+}
+
+/// Everything the shader takes from its uniforms to convert a sample
+struct Conversion {
+    offset: Vec3,
+    scale: Vec3,
+    matrix: Mat3,
+    brightness: f32,
+    contrast: f32,
+    chroma: Mat2,
+    gamma: f32,
+    transfer: TransferFunction,
+    output_transfer: TransferFunction,
+    ictcp: bool,
+    peak: f32,
+    tone_mapping: ToneMapping,
+    gamut: Option<(Mat3, GamutMapping)>,
+}
+
+impl Conversion {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            offset: settings.range.offset(),
+            scale: settings.range.scale(),
+            matrix: settings.matrix.to_rgb(settings.transfer),
+            brightness: settings.adjustments.brightness,
+            contrast: settings.adjustments.contrast,
+            chroma: settings.adjustments.chroma(),
+            gamma: settings.adjustments.clamped_gamma(),
+            transfer: settings.transfer,
+            output_transfer: settings.output_transfer(),
+            ictcp: settings.matrix == MatrixCoefficients::ICtCp,
+            peak: settings.peak(),
+            tone_mapping: settings.tone_mapping,
+            gamut: (settings.primaries != ColorPrimaries::Bt709).then(|| {
+                (
+                    settings.primaries.conversion(ColorPrimaries::Bt709),
+                    settings.gamut_mapping,
+                )
+            }),
+        }
+    }
+
+    /// Converts rows of samples with components from 0.0 to 1.0 to display encoded RGB,
+    /// in place
+    fn apply(&self, channels: &mut [Vec<f32>; 3]) {
+        each_rgb(channels, |sample| {
+            let yuv = (sample - self.offset) * self.scale;
+            let luma = (yuv.x - 0.5) * self.contrast + 0.5 + self.brightness;
+            let chroma = self.chroma * Vec2::new(yuv.y, yuv.z);
+
+            (self.matrix * Vec3::new(luma, chroma.x, chroma.y)).clamp(Vec3::ZERO, Vec3::ONE)
+        });
+
+        if self.ictcp {
+            each_rgb(channels, |signal| self.lms_to_rgb(signal));
+        }
+
+        if self.gamma != 1.0 {
+            let exponent = 1.0 / self.gamma;
+            each(channels, |c| c.powf(exponent));
+        }
+
+        self.to_display(channels);
+        each(channels, |c| c.clamp(0.0, 1.0));
+    }
+
+    fn to_display(&self, channels: &mut [Vec<f32>; 3]) {
+        if self.transfer == self.output_transfer && self.gamut.is_none() {
+            return;
+        }
+
+        self.to_linear(channels);
+
+        if self.transfer.is_hdr() {
+            each_rgb(channels, |linear| self.tone_map(linear));
+        }
+
+        if self.gamut.is_some() {
+            each_rgb(channels, |linear| self.map_gamut(linear));
+        }
+
+        from_linear(channels, self.output_transfer);
+    }
+
+    fn map_gamut(&self, linear: Vec3) -> Vec3 {
+        match self.gamut {
+            None => linear,
+            Some((conversion, GamutMapping::Clip)) => conversion * linear,
+            Some((conversion, GamutMapping::Compress)) => compress_gamut(conversion * linear),
+        }
+    }
+
+    /// EOTF of the frame, with SDR reference white at 1.0
+    fn to_linear(&self, channels: &mut [Vec<f32>; 3]) {
+        match self.transfer {
+            TransferFunction::Bt709 => each(channels, |c| c.powf(2.4)),
+            TransferFunction::Srgb => each(channels, srgb_to_linear),
+            TransferFunction::Linear => {}
+            TransferFunction::Pq => each(channels, |c| pq_decode(c) / REFERENCE_WHITE),
+            TransferFunction::Hlg => {
+                each(channels, hlg_inverse_oetf);
+                each_rgb(channels, |scene| self.hlg_ootf(scene) / REFERENCE_WHITE);
+            }
+        }
+    }
+
+    /// Converts the non-linear LMS of ICtCp frames to RGB with the same transfer function
+    fn lms_to_rgb(&self, signal: Vec3) -> Vec3 {
+        if !self.ictcp {
+            return signal;
+        }
+
+        match self.transfer {
+            TransferFunction::Pq => {
+                let rgb = LMS_TO_RGB * Vec3::from(signal.to_array().map(pq_decode));
+                rgb.to_array().map(pq_encode).into()
+            }
+            TransferFunction::Hlg => {
+                let rgb = LMS_TO_RGB * Vec3::from(signal.to_array().map(hlg_inverse_oetf));
+                rgb.to_array().map(hlg_oetf).into()
+            }
+            _ => signal,
+        }
+    }
+
+    /// BT.2100 HLG OOTF for a display of the peak luminance, in cd/m²
+    fn hlg_ootf(&self, scene: Vec3) -> Vec3 {
+        let peak = self.peak * REFERENCE_WHITE;
+        let gamma = 1.2 + 0.42 * (peak / 1000.0).log10();
+        let luminance = scene.dot(Vec3::new(0.2627, 0.6780, 0.0593));
+
+        peak * luminance.max(1e-6).powf(gamma - 1.0) * scene
+    }
+
+    /// Compresses linear HDR into the SDR range, scaling each color by its largest component
+    fn tone_map(&self, linear: Vec3) -> Vec3 {
+        let x = linear.max_element();
+        let peak = self.peak;
+
+        if x <= 0.0 {
+            return linear;
+        }
+
+        let mapped = match self.tone_mapping {
+            ToneMapping::Bt2390 => bt2390(x, peak),
+            ToneMapping::Hable => hable(x) / hable(peak),
+            ToneMapping::Reinhard => x * (1.0 + x / (peak * peak)) / (1.0 + x),
+            ToneMapping::Clip => x.min(1.0),
+        };
+
+        linear * (mapped / x)
+    }
+}
+
+/// Inverse EOTF of (SDR) `transfer`
+fn from_linear(channels: &mut [Vec<f32>; 3], transfer: TransferFunction) {
+    each(channels, |c| c.clamp(0.0, 1.0));
+
+    match transfer {
+        TransferFunction::Bt709 => each(channels, |c| c.powf(1.0 / 2.4)),
+        TransferFunction::Srgb => each(channels, linear_to_srgb),
+        _ => {}
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Smoothly compresses the distance of each component from the achromatic axis, up to
+/// a limit beyond the gamut boundary
+fn compress_gamut(linear: Vec3) -> Vec3 {
+    let achromatic = linear.max_element();
+
+    if achromatic <= 0.0 {
+        return linear;
+    }
+
+    let threshold: f32 = 0.8;
+    let limit = 1.2;
+    let power = 1.2;
+    let scale = (limit - threshold)
+        / (((1.0 - threshold) / (limit - threshold)).powf(-power) - 1.0).powf(1.0 / power);
+
+    let compress = |c: f32| {
+        let distance = (achromatic - c) / achromatic;
+
+        if distance <= threshold {
+            return c;
+        }
+
+        let excess = (distance - threshold) / scale;
+        let compressed = threshold + scale * excess / (1.0 + excess.powf(power)).powf(1.0 / power);
+
+        achromatic - compressed * achromatic
+    };
+
+    linear.to_array().map(compress).into()
+}
+
+/// SMPTE ST 2084 signal to cd/m²
+#[allow(clippy::excessive_precision)]
+fn pq_decode(signal: f32) -> f32 {
+    let p = signal.powf(1.0 / 78.84375);
+
+    10000.0 * ((p - 0.8359375).max(0.0) / (18.8515625 - 18.6875 * p)).powf(1.0 / 0.1593017578125)
+}
+
+/// cd/m² to SMPTE ST 2084 signal
+#[allow(clippy::excessive_precision)]
+fn pq_encode(luminance: f32) -> f32 {
+    let y = (luminance / 10000.0).clamp(0.0, 1.0).powf(0.1593017578125);
+
+    ((0.8359375 + 18.8515625 * y) / (1.0 + 18.6875 * y)).powf(78.84375)
+}
+
+/// BT.2100 HLG signal to normalized scene light
+#[allow(clippy::excessive_precision)]
+fn hlg_inverse_oetf(signal: f32) -> f32 {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+
+    if signal <= 0.5 {
+        signal * signal / 3.0
+    } else {
+        (((signal - c) / a).exp() + b) / 12.0
+    }
+}
+
+/// Normalized scene light to BT.2100 HLG signal
+#[allow(clippy::excessive_precision)]
+fn hlg_oetf(scene: f32) -> f32 {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+    let e = scene.max(0.0);
+
+    if e <= 1.0 / 12.0 {
+        (3.0 * e).sqrt()
+    } else {
+        a * (12.0 * e - b).max(1e-6).ln() + c
+    }
+}
+
+/// BT.2390 EETF with a target of SDR reference white
+fn bt2390(x: f32, peak: f32) -> f32 {
+    let source_max = pq_encode(peak * REFERENCE_WHITE);
+    let target_max = pq_encode(REFERENCE_WHITE) / source_max;
+    let knee = 1.5 * target_max - 0.5;
+    let e = (pq_encode(x * REFERENCE_WHITE) / source_max).min(1.0);
+
+    if e <= knee {
+        return x;
+    }
+
+    let t = (e - knee) / (1.0 - knee);
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let spline = (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+        + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+        + (-2.0 * t3 + 3.0 * t2) * target_max;
+
+    pq_decode(spline * source_max) / REFERENCE_WHITE
+}
+
+/// Hable's filmic curve (Uncharted 2)
+fn hable(x: f32) -> f32 {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_data_is_an_error() {
+        let yuv = Yuv {
+            format: Format::NV12,
+            dimensions: Size {
+                width: 5,
+                height: 3,
+            },
+            data: vec![0; 5 * 3 + 2 * 3 * 2 - 1].into(),
+            ..Yuv::default()
+        };
+
+        assert!(matches!(
+            to_rgba(&yuv),
+            Err(Error::DataLength {
+                expected: 27,
+                found: 26
+            })
+        ));
+    }
+
+    /// I420 frame whose chroma is black in the first column and white in the last
+    fn i420(width: u32, height: u32, len: usize) -> Renderable {
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let u = (0..chroma_height).flat_map(|_| {
+            (0..chroma_width).map(move |x| if x + 1 == chroma_width { 255 } else { 128 })
+        });
+
+        let mut data: Vec<u8> = std::iter::repeat_n(128, (width * height) as usize)
+            .chain(u.clone())
+            .chain(u)
+            .collect();
+        data.truncate(len);
+
+        Renderable::from(Yuv {
+            format: Format::I420,
+            dimensions: Size { width, height },
+            data: data.into(),
+            ..Yuv::default()
+        })
+    }
+
+    #[test]
+    fn odd_widths_keep_their_chroma_stride() {
+        let settings = Settings::default();
+        let rgba = rgba(&i420(5, 3, usize::MAX), &settings).expect("whole frame");
+
+        // each row ends on the chroma of the last column, not of the next row
+        for row in rgba.chunks_exact(5 * 4) {
+            assert_ne!(row[..4], row[16..]);
+            assert_eq!(row[16..], rgba[16..20]);
+        }
+
+        for (width, height) in [(1, 1), (1, 4), (4, 1)] {
+            assert!(super::rgba(&i420(width, height, usize::MAX), &settings).is_ok());
+        }
+    }
+
+    #[test]
+    fn short_renderables_are_an_error() {
+        let settings = Settings::default();
+
+        assert!(matches!(
+            rgba(&i420(5, 3, 5 * 3 + 2 * 3 * 2 - 1), &settings),
+            Err(Error::DataLength {
+                expected: 27,
+                found: 26
+            })
+        ));
+        assert!(matches!(rgba(&i420(0, 3, 0), &settings), Err(Error::Empty)));
+    }
+}
@@ -1,6 +1,16 @@
+pub mod cpu;
+pub mod lut;
+pub mod post;
+
+mod color;
 mod program;
+mod viewer;
 mod yuv;
 
+pub use color::{ColorPrimaries, ColorRange, MatrixCoefficients, TransferFunction};
+pub use lut::Lut;
+pub use post::PostProcess;
 pub use program::*;
-pub(crate) use yuv::Renderable;
-pub use yuv::{Format, Size, Yuv};
+pub use viewer::{viewer, Viewer};
+pub use yuv::{ChromaLocation, Data, FieldOrder, Format, Size, Yuv};
+pub(crate) use yuv::{Renderable, WeakData};
@@ -0,0 +1,266 @@
+//! Color look-up tables in the Adobe / DaVinci Resolve `.cube` format
+use std::path::Path;
+
+use thiserror::Error;
+
+/// Largest supported `LUT_3D_SIZE`
+const MAX_3D_SIZE: u32 = 256;
+
+/// Largest supported `LUT_1D_SIZE`
+const MAX_1D_SIZE: u32 = 65536;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("io error: {0:?}")]
+    IoError(#[from] std::io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("neither LUT_1D_SIZE nor LUT_3D_SIZE is specified")]
+    MissingSize,
+    #[error("expected {expected} table entries, found {found}")]
+    EntryCount { expected: usize, found: usize },
+}
+
+/// Look-up table applied to the RGB of each frame after conversion
+///
+/// A `.cube` file holds a 1D table, a 3D table, or (as written by Resolve)
+/// a 1D shaper table followed by a 3D table.
+#[derive(Debug, Clone)]
+pub struct Lut {
+    pub(crate) shaper: Option<Table>,
+    pub(crate) cube: Option<Table>,
+}
+
+/// Entries of a 1D or 3D table, with red varying fastest
+#[derive(Debug, Clone)]
+pub(crate) struct Table {
+    pub size: u32,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Reads a `.cube` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of a `.cube` file
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut size_1d = None;
+        let mut size_3d = None;
+        let mut domain = ([0.0; 3], [1.0; 3]);
+        // line of the last DOMAIN_MIN or DOMAIN_MAX, where an empty domain is reported
+        let mut domain_line = 0;
+        let mut range_1d = None;
+        let mut range_3d = None;
+        let mut entries = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let syntax = |message: &str| Error::Syntax {
+                line: i + 1,
+                message: message.to_owned(),
+            };
+
+            let line = line.trim();
+            let mut words = line.split_whitespace();
+
+            let Some(keyword) = words.next().filter(|word| !word.starts_with('#')) else {
+                continue;
+            };
+
+            match keyword {
+                "LUT_1D_SIZE" => {
+                    size_1d = Some(
+                        size(words, MAX_1D_SIZE).ok_or_else(|| syntax("invalid LUT_1D_SIZE"))?,
+                    );
+                }
+                "LUT_3D_SIZE" => {
+                    size_3d = Some(
+                        size(words, MAX_3D_SIZE).ok_or_else(|| syntax("invalid LUT_3D_SIZE"))?,
+                    );
+                }
+                "DOMAIN_MIN" => {
+                    domain.0 = numbers(words).ok_or_else(|| syntax("invalid DOMAIN_MIN"))?;
+                    domain_line = i + 1;
+                }
+                "DOMAIN_MAX" => {
+                    domain.1 = numbers(words).ok_or_else(|| syntax("invalid DOMAIN_MAX"))?;
+                    domain_line = i + 1;
+                }
+                "LUT_1D_INPUT_RANGE" => {
+                    let range = numbers(words)
+                        .map(|[min, max]| ([min; 3], [max; 3]))
+                        .filter(is_valid)
+                        .ok_or_else(|| syntax("invalid LUT_1D_INPUT_RANGE"))?;
+                    range_1d = Some(range);
+                }
+                "LUT_3D_INPUT_RANGE" => {
+                    let range = numbers(words)
+                        .map(|[min, max]| ([min; 3], [max; 3]))
+                        .filter(is_valid)
+                        .ok_or_else(|| syntax("invalid LUT_3D_INPUT_RANGE"))?;
+                    range_3d = Some(range);
+                }
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    entries.push(
+                        numbers(line.split_whitespace())
+                            .ok_or_else(|| syntax("expected three values per entry"))?,
+                    );
+                }
+                // TITLE and vendor specific keywords don't affect the table
+                _ => {}
+            }
+        }
+
+        if size_1d.is_none() && size_3d.is_none() {
+            return Err(Error::MissingSize);
+        }
+
+        // the shader divides by the width of the domain
+        let uses_domain =
+            (size_1d.is_some() && range_1d.is_none()) || (size_3d.is_some() && range_3d.is_none());
+        if uses_domain && !is_valid(&domain) {
+            return Err(Error::Syntax {
+                line: domain_line,
+                message: "DOMAIN_MIN must be below DOMAIN_MAX".to_owned(),
+            });
+        }
+
+        let expected = size_1d.unwrap_or(0) as usize + size_3d.map_or(0, |n| n.pow(3)) as usize;
+        if entries.len() != expected {
+            return Err(Error::EntryCount {
+                expected,
+                found: entries.len(),
+            });
+        }
+
+        let cube_entries = entries.split_off(size_1d.unwrap_or(0) as usize);
+
+        Ok(Self {
+            shaper: size_1d.map(|size| {
+                let (domain_min, domain_max) = range_1d.unwrap_or(domain);
+
+                Table {
+                    size,
+                    domain_min,
+                    domain_max,
+                    entries,
+                }
+            }),
+            cube: size_3d.map(|size| {
+                let (domain_min, domain_max) = range_3d.unwrap_or(domain);
+
+                Table {
+                    size,
+                    domain_min,
+                    domain_max,
+                    entries: cube_entries,
+                }
+            }),
+        })
+    }
+}
+
+/// Whether the minimum of each channel is below its maximum
+fn is_valid((min, max): &([f32; 3], [f32; 3])) -> bool {
+    min.iter().zip(max).all(|(min, max)| min < max)
+}
+
+fn size<'a>(mut words: impl Iterator<Item = &'a str>, max: u32) -> Option<u32> {
+    words
+        .next()?
+        .parse()
+        .ok()
+        .filter(|size| (2..=max).contains(size))
+}
+
+fn numbers<'a, const N: usize>(words: impl Iterator<Item = &'a str>) -> Option<[f32; N]> {
+    let numbers = words
+        .map(str::parse)
+        .collect::<Result<Vec<f32>, _>>()
+        .ok()?;
+
+    numbers.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identity 3D table of size 2, with red varying fastest
+    const CUBE: &str = "0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    #[test]
+    fn tables_and_keywords() {
+        let lut = Lut::parse(&format!(
+            "# comment\nTITLE \"identity\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n{CUBE}"
+        ))
+        .expect("valid cube");
+
+        assert!(lut.shaper.is_none());
+
+        let cube = lut.cube.expect("3D table");
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.domain_max, [2.0; 3]);
+        assert_eq!(cube.entries.len(), 8);
+        assert_eq!(cube.entries[1], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn shaper_followed_by_cube() {
+        let lut = Lut::parse(&format!(
+            "LUT_1D_SIZE 3\nLUT_1D_INPUT_RANGE -0.5 1.5\nLUT_3D_SIZE 2\n\
+             0 0 0\n0.5 0.5 0.5\n1 1 1\n{CUBE}"
+        ))
+        .expect("valid cube");
+
+        let shaper = lut.shaper.expect("1D table");
+        assert_eq!(shaper.size, 3);
+        assert_eq!(shaper.domain_min, [-0.5; 3]);
+        assert_eq!(shaper.entries[1], [0.5; 3]);
+
+        let cube = lut.cube.expect("3D table");
+        assert_eq!(cube.domain_min, [0.0; 3]);
+        assert_eq!(cube.entries.len(), 8);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(matches!(Lut::parse(CUBE), Err(Error::MissingSize)));
+        assert!(matches!(
+            Lut::parse("LUT_3D_SIZE 2\n0 0 0\n"),
+            Err(Error::EntryCount {
+                expected: 8,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            Lut::parse("LUT_3D_SIZE 1\n0 0 0\n"),
+            Err(Error::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            Lut::parse("LUT_1D_SIZE 2\n0 0\n1 1 1\n"),
+            Err(Error::Syntax { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn empty_domains_are_rejected() {
+        assert!(matches!(
+            Lut::parse(&format!("LUT_3D_SIZE 2\nDOMAIN_MAX 1 0 1\n{CUBE}")),
+            Err(Error::Syntax { line: 2, .. })
+        ));
+        assert!(matches!(
+            Lut::parse(&format!(
+                "LUT_3D_SIZE 2\nDOMAIN_MIN 1 1 1\nDOMAIN_MAX 0.5 1 1\n{CUBE}"
+            )),
+            Err(Error::Syntax { line: 3, .. })
+        ));
+        assert!(matches!(
+            Lut::parse("LUT_1D_SIZE 2\nLUT_1D_INPUT_RANGE 1 1\n0 0 0\n1 1 1\n"),
+            Err(Error::Syntax { line: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! Post-processing hooks written in WGSL, applied to frames after conversion
+use std::path::Path;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+/// Hook applied when no [`PostProcess`] is set
+const IDENTITY: &str = "fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    return rgb;
+}
+";
+
+/// Name of the hook's source in errors
+const PATH: &str = "post.wgsl";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("io error: {0:?}")]
+    IoError(#[from] std::io::Error),
+    #[error("failed to parse post-processing hook:\n{0}")]
+    Parse(String),
+    #[error("post-processing hook failed validation:\n{0}")]
+    Validation(String),
+}
+
+/// WGSL function applied to each frame after it was converted to RGB
+///
+/// The source has to define `fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32>`, which
+/// receives the converted color, encoded for the target, and the texture coordinates of
+/// the frame. It can declare functions and constants of its own and read the values set
+/// with [`Program::update_post_uniforms`] as `uniforms.post`, an `array<vec4<f32>, 4>`.
+///
+/// [`Program::update_post_uniforms`]: crate::Program::update_post_uniforms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostProcess {
+    source: Arc<str>,
+}
+
+impl PostProcess {
+    /// Reads the hook from a `.wgsl` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(&std::fs::read_to_string(path)?)
+    }
+
+    /// Validates the hook together with the shader it is composed into
+    pub fn new(source: &str) -> Result<Self, Error> {
+        let shader = compose(source);
+
+        let module = naga::front::wgsl::parse_str(&shader)
+            .map_err(|error| Error::Parse(error.emit_to_string_with_path(&shader, PATH)))?;
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::default(),
+        )
+        .validate(&module)
+        .map_err(|error| Error::Validation(error.emit_to_string_with_path(&shader, PATH)))?;
+
+        Ok(Self {
+            source: source.into(),
+        })
+    }
+
+    /// Source of the shader with the hook composed in
+    pub(crate) fn shader(&self) -> String {
+        compose(&self.source)
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            source: IDENTITY.into(),
+        }
+    }
+}
+
+/// The hook goes first, so that lines of errors within it match its source
+fn compose(source: &str) -> String {
+    format!("{source}\n{}", include_str!("program/shader.wgsl"))
+}
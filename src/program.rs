@@ -1,105 +1,693 @@
+mod converter;
 mod pipeline;
+mod privacy;
+mod screenshot;
+mod wall;
 
 use std::cell::RefCell;
-use std::ops::Deref;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use pipeline::Pipeline;
 
+use glam::Mat2;
 use iced::mouse;
-use iced::widget::shader;
-use iced::Rectangle;
+use iced::widget::{image, shader};
+use iced::{Border, Color, Rectangle};
 use shader::wgpu;
 
-use self::pipeline::Uniforms;
-use crate::{Renderable, Size, Yuv};
+pub use self::converter::Converter;
+pub use self::privacy::{
+    PrivacyRegion, Redaction, RegionShape, MAX_PRIVACY_REGIONS, MAX_PRIVACY_VERTICES,
+};
+pub use self::screenshot::{Resolution, Screenshot, Snapshot};
+pub use self::wall::{VideoWall, WallError, MAX_WALL_TILES};
+
+use self::pipeline::{Uniforms, READBACK_FORMAT};
+pub(crate) use self::privacy::Regions;
+use self::screenshot::Request;
+use crate::{
+    cpu, ChromaLocation, ColorPrimaries, ColorRange, Data, FieldOrder, Lut, MatrixCoefficients,
+    PostProcess, Renderable, Size, TransferFunction, WeakData, Yuv,
+};
 
 pub struct Program {
-    dimensions: Size<u32>,
-    sampling_factor: f32,
-    data: RefCell<Option<Renderable>>,
+    settings: Settings,
+    frame: Frame,
+    /// Storage and sequence of the latest frame, to skip frames which were already uploaded
+    source: WeakData,
+    sequence: Option<u64>,
+    /// Version of the latest frame which reached the GPU
+    uploaded: Arc<AtomicU64>,
+    lut: Option<Arc<Lut>>,
+    mask: Option<Mask>,
+    overlays: Vec<Overlay>,
+    opacity: f32,
+    post: PostProcess,
+    /// Frame converted on the CPU, with the version and settings it was converted with
+    image: RefCell<Option<(u64, Settings, image::Handle)>>,
+    screenshots: RefCell<Vec<Request>>,
+}
+
+/// Frame of a [`Program`], with a version which is unique across programs
+#[derive(Debug, Clone)]
+struct Frame {
+    version: u64,
+    yuv: Renderable,
+    /// Regions the frame differs in from an earlier one, if only those have to be uploaded
+    dirty: Option<Dirty>,
+}
+
+/// Regions of a frame which changed since the frame of version `base`, in luma samples
+#[derive(Debug, Clone)]
+pub(crate) struct Dirty {
+    pub base: u64,
+    pub regions: Arc<[Rectangle<u32>]>,
+}
+
+impl Frame {
+    fn new(yuv: Yuv, dirty: Option<Dirty>) -> Self {
+        // 0 is left for no frame
+        static VERSION: AtomicU64 = AtomicU64::new(1);
+
+        Self {
+            version: VERSION.fetch_add(1, Ordering::Relaxed),
+            yuv: yuv.into(),
+            dirty,
+        }
+    }
 }
 
 impl Program {
     pub fn new(yuv: Yuv) -> Self {
-        let renderable = Renderable::from(yuv);
+        let mut settings = Settings::default();
+        settings.update(&yuv);
 
         Self {
-            dimensions: renderable.dimensions(),
-            sampling_factor: renderable.downsampling_factor(),
-            data: RefCell::new(Some(renderable)),
+            settings,
+            source: yuv.data.downgrade(),
+            sequence: yuv.sequence,
+            frame: Frame::new(yuv, None),
+            uploaded: Arc::new(AtomicU64::new(0)),
+            lut: None,
+            mask: None,
+            overlays: Vec::new(),
+            opacity: 1.0,
+            post: PostProcess::default(),
+            image: RefCell::new(None),
+            screenshots: RefCell::new(Vec::new()),
         }
     }
 
+    /// Sets the [`ChromaFilter`] used to upsample the chroma planes of subsampled frames.
+    pub fn chroma_filter(mut self, filter: ChromaFilter) -> Self {
+        self.settings.chroma_filter = filter;
+        self
+    }
+
+    /// Enables generating mipmaps for each uploaded frame, so that the frame can be
+    /// downscaled heavily (e.g. in thumbnails) without aliasing.
+    pub fn mipmaps(mut self, enabled: bool) -> Self {
+        self.settings.mipmaps = enabled;
+        self
+    }
+
+    /// Sets the [`Buffering`] of uploaded frames.
+    pub fn buffering(mut self, buffering: Buffering) -> Self {
+        self.settings.buffering = buffering;
+        self
+    }
+
+    /// Sets where frames are converted to RGB, see [`Conversion`].
+    pub fn conversion(mut self, conversion: Conversion) -> Self {
+        self.settings.conversion = conversion;
+        self
+    }
+
+    /// Sets the [`Deinterlace`] mode applied to interlaced frames.
+    pub fn deinterlace(mut self, deinterlace: Deinterlace) -> Self {
+        self.settings.deinterlace = deinterlace;
+        self
+    }
+
+    /// Sets the [`ToneMapping`] operator used to render HDR frames to SDR.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.settings.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// Sets how colors outside of the BT.709 / sRGB gamut are handled when converting
+    /// frames with wider [`ColorPrimaries`].
+    pub fn gamut_mapping(mut self, gamut_mapping: GamutMapping) -> Self {
+        self.settings.gamut_mapping = gamut_mapping;
+        self
+    }
+
+    /// Sets the [`PictureAdjustments`] applied to each frame.
+    pub fn adjustments(mut self, adjustments: PictureAdjustments) -> Self {
+        self.settings.adjustments = adjustments;
+        self
+    }
+
+    /// Updates the [`PictureAdjustments`], which takes effect on the next draw
+    /// without uploading the frame again.
+    pub fn update_adjustments(&mut self, adjustments: PictureAdjustments) {
+        self.settings.adjustments = adjustments;
+    }
+
+    /// Sets a [`Lut`] applied to the RGB of each frame, mixed in by `strength` from 0.0 to 1.0.
+    pub fn lut(mut self, lut: impl Into<Arc<Lut>>, strength: f32) -> Self {
+        self.update_lut(lut, strength);
+        self
+    }
+
+    /// Replaces the [`Lut`], which is only uploaded again if it isn't the same `Arc` as
+    /// the one drawn last.
+    pub fn update_lut(&mut self, lut: impl Into<Arc<Lut>>, strength: f32) {
+        self.settings.lut_strength = strength;
+        self.lut = Some(lut.into());
+    }
+
+    /// Updates how much of the [`Lut`] is mixed in without uploading it again.
+    pub fn update_lut_strength(&mut self, strength: f32) {
+        self.settings.lut_strength = strength;
+    }
+
+    /// Sets the [`Border`] of the frame, rounding its corners and drawing a border
+    /// within its bounds like the border of a container.
+    pub fn border(mut self, border: Border) -> Self {
+        self.settings.border = border;
+        self
+    }
+
+    pub fn update_border(&mut self, border: Border) {
+        self.settings.border = border;
+    }
+
+    /// Sets a [`Mask`] whose coverage is applied to the alpha of the frame.
+    pub fn mask(mut self, mask: Mask) -> Self {
+        self.update_mask(Some(mask));
+        self
+    }
+
+    /// Sets or removes the [`Mask`], which is only uploaded again if its [`Data`] changed.
+    pub fn update_mask(&mut self, mask: Option<Mask>) {
+        self.mask = mask.filter(Mask::is_valid);
+    }
+
+    /// Adds an [`Overlay`] drawn over the frame, on top of the overlays added before it.
+    pub fn overlay(mut self, overlay: Overlay) -> Self {
+        if overlay.is_valid() {
+            self.overlays.push(overlay);
+        }
+        self
+    }
+
+    /// Replaces the [`Overlay`]s, whose bitmaps are only uploaded again if their [`Data`]
+    /// changed.
+    ///
+    /// Overlays are drawn with the frame, so they aren't part of screenshots or the frames
+    /// of a [`Converter`].
+    pub fn update_overlays(&mut self, overlays: impl IntoIterator<Item = Overlay>) {
+        self.overlays = overlays.into_iter().filter(Overlay::is_valid).collect();
+    }
+
+    /// Sets the opacity of the frame from 0.0 to 1.0, e.g. to fade it in or out.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn update_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    /// Enables cross-fading from the previous frame to each new frame over `duration`,
+    /// e.g. for slideshows or when switching sources.
+    ///
+    /// The [`Program`] has to be redrawn while the fade lasts. It keeps another frame on
+    /// the GPU, so [`Program::update_regions`] uploads whole frames.
+    pub fn cross_fade(mut self, duration: Duration) -> Self {
+        self.settings.cross_fade = Some(duration);
+        self
+    }
+
+    /// Sets or disables the cross-fade to new frames, see [`Program::cross_fade`].
+    pub fn update_cross_fade(&mut self, duration: Option<Duration>) {
+        self.settings.cross_fade = duration;
+    }
+
+    /// Sets a [`PostProcess`] hook applied to each frame after conversion.
+    ///
+    /// The shader is compiled again with the hook the next time the [`Program`] is drawn.
+    pub fn post_process(mut self, post: PostProcess) -> Self {
+        self.post = post;
+        self
+    }
+
+    pub fn update_post_process(&mut self, post: PostProcess) {
+        self.post = post;
+    }
+
+    /// Updates the values the [`PostProcess`] hook reads as `uniforms.post`.
+    pub fn update_post_uniforms(&mut self, uniforms: [[f32; 4]; 4]) {
+        self.settings.post_uniforms = uniforms;
+    }
+
+    /// Sets a [`ChromaKey`] keying out a background color, e.g. of a green screen.
+    pub fn chroma_key(mut self, chroma_key: ChromaKey) -> Self {
+        self.settings.chroma_key = Some(chroma_key);
+        self
+    }
+
+    /// Sets or removes the [`ChromaKey`].
+    pub fn update_chroma_key(&mut self, chroma_key: Option<ChromaKey>) {
+        self.settings.chroma_key = chroma_key;
+    }
+
+    /// Hides [`PrivacyRegion`]s of the frame when it is drawn, e.g. faces or license plates.
+    pub fn privacy_regions(mut self, regions: &[PrivacyRegion]) -> Self {
+        self.update_privacy_regions(regions);
+        self
+    }
+
+    /// Replaces the [`PrivacyRegion`]s, e.g. with the regions tracked in the next frame.
+    ///
+    /// Up to [`MAX_PRIVACY_REGIONS`] regions and [`MAX_PRIVACY_VERTICES`] vertices of
+    /// polygons are drawn as they are, further regions are hidden by their bounding box.
+    pub fn update_privacy_regions(&mut self, regions: &[PrivacyRegion]) {
+        self.settings.privacy = Regions::new(regions);
+    }
+
+    /// Sets the frame to draw, which is uploaded on the next draw unless it shares its
+    /// [`Data`] or its [`Yuv::sequence`] with the previous frame.
+    ///
+    /// [`Data`]: crate::Data
     pub fn update_frame(&mut self, yuv: Yuv) {
-        *self.data.borrow_mut() = Some(yuv.into());
+        self.update(yuv, None);
     }
-}
 
-impl<Message> shader::Program<Message> for Program {
-    type State = ();
-    type Primitive = Primitive;
+    /// Sets a frame which only differs from the previous one within `regions`, given in
+    /// luma samples, so that only those regions are uploaded (e.g. for screen sharing).
+    ///
+    /// Regions are uploaded on their own with [`Buffering::Single`], otherwise the whole
+    /// frame is uploaded since the texture written to holds an older frame.
+    pub fn update_regions(&mut self, yuv: Yuv, regions: &[Rectangle<u32>]) {
+        // regions of frames which weren't uploaded yet accumulate
+        let dirty = if self.is_frame_uploaded() {
+            Some(Dirty {
+                base: self.frame.version,
+                regions: regions.into(),
+            })
+        } else {
+            self.frame.dirty.as_ref().map(|dirty| Dirty {
+                base: dirty.base,
+                regions: dirty.regions.iter().chain(regions).copied().collect(),
+            })
+        };
 
-    fn draw(
-        &self,
-        _state: &Self::State,
-        _cursor: mouse::Cursor,
-        bounds: Rectangle,
-    ) -> Self::Primitive {
-        Primitive(Mutex::new(match self.data.borrow_mut().take() {
-            Some(yuv) => State::Pending { yuv, bounds },
+        self.update(yuv, dirty);
+    }
 
-            _ => State::Prepared {
-                bounds,
-                image_dimensions: self.dimensions,
-                sampling_factor: self.sampling_factor,
-            },
-        }))
+    fn update(&mut self, yuv: Yuv, dirty: Option<Dirty>) {
+        self.settings.update(&yuv);
+
+        if self.source.is(&yuv.data) || (yuv.sequence.is_some() && yuv.sequence == self.sequence) {
+            return;
+        }
+
+        self.source = yuv.data.downgrade();
+        self.sequence = yuv.sequence;
+        self.frame = Frame::new(yuv, dirty);
+    }
+
+    /// Whether the latest frame reached the GPU, i.e. it was uploaded and is drawn
+    pub fn is_frame_uploaded(&self) -> bool {
+        self.uploaded.load(Ordering::Acquire) == self.frame.version
+    }
+
+    /// Requests the current frame as it is rendered, after color conversion and adjustments,
+    /// for saving screenshots or thumbnails.
+    ///
+    /// The frame is rendered the next time the [`Program`] is drawn, so a redraw has to
+    /// follow the request. [`Resolution::Display`] also includes the stretch to the bounds
+    /// of the widget.
+    pub fn screenshot(&self, resolution: Resolution) -> Screenshot {
+        let (request, screenshot) = Request::new(resolution);
+        self.screenshots.borrow_mut().push(request);
+
+        screenshot
+    }
+
+    /// Converts the latest frame with the settings of the [`Program`] on the CPU, like
+    /// [`cpu::to_rgba`], or returns `None` without a frame or if the frame is shorter than
+    /// its dimensions
+    ///
+    /// [`Lut`]s, post-processing and keying are not applied, and blurred privacy regions
+    /// approximate the Gaussian blur by box blurs.
+    pub fn to_rgba(&self) -> Option<Vec<u8>> {
+        cpu::rgba(&self.frame.yuv, &self.settings).ok()
+    }
+
+    /// The latest frame converted on the CPU, for renderers which can't draw the shader
+    pub(crate) fn image(&self) -> Option<image::Handle> {
+        let mut image = self.image.borrow_mut();
+        let converted = image.as_ref().is_some_and(|(version, settings, _)| {
+            *version == self.frame.version && *settings == self.settings
+        });
+
+        if !converted {
+            *image = cpu::handle(&self.frame.yuv, &self.settings)
+                .ok()
+                .map(|handle| (self.frame.version, self.settings, handle));
+        }
+
+        image.as_ref().map(|(_, _, handle)| handle.clone())
     }
 }
 
-#[derive(Debug)]
-pub struct Primitive(Mutex<State>);
+/// Filter used to upsample subsampled chroma planes to the luma resolution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChromaFilter {
+    /// Hardware bilinear filtering
+    #[default]
+    Bilinear,
+    /// Catmull-Rom bicubic filtering, which keeps saturated edges (e.g. red text) sharp
+    ///
+    /// Only applies where the frame is magnified. Interlaced frames which are
+    /// deinterlaced with any mode other than [`Deinterlace::Weave`] fall back to bilinear
+    /// filtering, as does the previous frame while cross-fading.
+    Bicubic,
+}
 
-#[derive(Debug)]
-enum State {
-    Pending {
-        yuv: Renderable,
-        bounds: Rectangle,
-    },
-    Prepared {
-        image_dimensions: Size<u32>,
-        sampling_factor: f32,
-        bounds: Rectangle,
-    },
+/// Number of textures frames are uploaded to in turn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Buffering {
+    /// Frames are written to the texture which is displayed, so large frames stall
+    /// rendering until they are copied
+    #[default]
+    Single,
+    /// Frames are staged and copied to a texture which isn't displayed, then shown on
+    /// the first draw after the copy completed
+    ///
+    /// Drawing never waits for a copy: a frame set while no texture is free is skipped,
+    /// and the latest frame is uploaded on a later draw once one is.
+    Double,
+    /// Like [`Buffering::Double`], with another texture so that a frame can be staged
+    /// while the previous one is still being copied
+    Triple,
 }
 
-impl State {
-    fn bounds(&self) -> Rectangle {
+impl Buffering {
+    pub(crate) fn frames(self) -> usize {
         match self {
-            Self::Pending { bounds, .. } | Self::Prepared { bounds, .. } => *bounds,
+            Self::Single => 1,
+            Self::Double => 2,
+            Self::Triple => 3,
         }
     }
+}
 
-    fn image_dimensions(&self) -> Size<u32> {
-        match self {
-            Self::Prepared {
-                image_dimensions, ..
-            } => *image_dimensions,
-            Self::Pending { yuv, .. } => yuv.dimensions(),
+/// Where frames are converted to RGB
+///
+/// Devices without compute shaders (e.g. WebGL) always convert in the fragment shader.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Conversion {
+    /// In the fragment shader, at display resolution on every draw
+    #[default]
+    Fragment,
+    /// Once per frame by a compute pass into an `Rgba8Unorm` texture, which is drawn
+    /// until the frame or the settings change
+    Compute,
+    /// Like [`Conversion::Compute`], into an `Rgba16Float` texture which keeps HDR
+    /// frames beyond SDR white on floating point targets
+    ComputeHdr,
+}
+
+/// Deinterlacing applied to frames whose [`FieldOrder`] is interlaced
+///
+/// Each frame is rendered once, so the modes which only keep one field
+/// show the field which was captured first. Modes other than [`Deinterlace::Weave`]
+/// upsample chroma bilinearly, even with [`ChromaFilter::Bicubic`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Deinterlace {
+    /// Both fields are shown as they are, which shows combing on motion
+    #[default]
+    Weave,
+    /// The first field is shown, with the lines of the second field interpolated
+    Bob,
+    /// Neighbouring lines are blended, trading combing for vertical blur
+    Blend,
+    /// Lines of the second field are kept where they didn't change since the
+    /// previous frame and interpolated from the first field where they did
+    Adaptive,
+}
+
+/// Operator compressing the luminance of HDR frames into the SDR range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// BT.2390 EETF, rolling off highlights in the PQ domain
+    #[default]
+    Bt2390,
+    /// Hable's filmic curve
+    Hable,
+    /// Extended Reinhard, mapping the peak luminance to SDR white
+    Reinhard,
+    /// Clips everything brighter than SDR white
+    Clip,
+}
+
+/// Handling of colors which can't be represented with the primaries of the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GamutMapping {
+    /// Out of gamut components are clipped, which may shift their hue
+    #[default]
+    Clip,
+    /// Saturation close to and beyond the gamut boundary is compressed smoothly
+    ///
+    /// Components up to 20% beyond the boundary are brought into the gamut and further
+    /// ones are clipped. Saturated colors which are already within the gamut are
+    /// desaturated slightly as well, so that gradients crossing the boundary stay smooth.
+    Compress,
+}
+
+/// Picture corrections applied while converting frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PictureAdjustments {
+    /// Offset added to luma, from -1.0 to 1.0
+    pub brightness: f32,
+    /// Scale of luma around mid-gray, 1.0 leaves it unchanged
+    pub contrast: f32,
+    /// Scale of chroma, 0.0 is grayscale and 1.0 leaves it unchanged
+    pub saturation: f32,
+    /// Rotation of chroma in radians
+    pub hue: f32,
+    /// Gamma correction of the converted RGB, values above 1.0 brighten midtones
+    ///
+    /// Values below 0.01 are treated as 0.01.
+    pub gamma: f32,
+}
+
+impl PictureAdjustments {
+    /// Rotation and scale applied to chroma centered on zero
+    pub(crate) fn chroma(&self) -> Mat2 {
+        Mat2::from_angle(self.hue) * self.saturation
+    }
+
+    /// Gamma clamped to a positive minimum, since the RGB is raised to its reciprocal
+    pub(crate) fn clamped_gamma(&self) -> f32 {
+        self.gamma.max(0.01)
+    }
+}
+
+impl Default for PictureAdjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            gamma: 1.0,
         }
     }
+}
 
-    fn sampling_factor(&self) -> f32 {
-        match self {
-            Self::Prepared {
-                sampling_factor, ..
-            } => *sampling_factor,
-            Self::Pending { yuv, .. } => yuv.downsampling_factor(),
+/// Keying of a background color by the distance of chroma to the chroma of the key color,
+/// so that the frame can be composited over other content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaKey {
+    /// Color keyed out
+    pub color: Color,
+    /// Chroma distance to the key color within which pixels are transparent, from 0.0 to 1.0
+    pub similarity: f32,
+    /// Chroma distance beyond the similarity over which pixels become opaque
+    pub smoothness: f32,
+    /// Chroma distance beyond the similarity over which the key color is desaturated
+    /// from pixels which remain, suppressing its spill
+    pub spill: f32,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            color: Color::from_rgb(0.0, 1.0, 0.0),
+            similarity: 0.4,
+            smoothness: 0.08,
+            spill: 0.1,
+        }
+    }
+}
+
+/// Alpha mask stretched over the bounds of a [`Program`], e.g. to clip the frame to a shape
+#[derive(Debug, Clone, Default)]
+pub struct Mask {
+    /// Coverage of each pixel from 0 to 255, row by row
+    pub alpha: Data,
+    pub dimensions: Size<u32>,
+}
+
+impl Mask {
+    /// Whether there is coverage for every pixel
+    fn is_valid(&self) -> bool {
+        let Size { width, height } = self.dimensions;
+
+        let len = (width as usize).checked_mul(height as usize);
+
+        width > 0 && height > 0 && len.is_some_and(|len| self.alpha.len() >= len)
+    }
+}
+
+/// RGBA bitmap drawn over the frame, e.g. a decoded PGS or DVB subtitle or on-screen graphics
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    /// sRGB pixels with straight alpha, row by row
+    pub rgba: Data,
+    pub dimensions: Size<u32>,
+    /// Bounds in pixels of the frame, which the bitmap is stretched to
+    pub bounds: Rectangle,
+}
+
+impl Overlay {
+    /// Whether there are pixels for the whole bitmap
+    fn is_valid(&self) -> bool {
+        let Size { width, height } = self.dimensions;
+
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|len| len.checked_mul(4));
+
+        width > 0 && height > 0 && len.is_some_and(|len| self.rgba.len() >= len)
+    }
+}
+
+/// Luminance of SDR reference white in cd/m² (BT.2408)
+pub(crate) const REFERENCE_WHITE: f32 = 203.0;
+
+/// Peak luminance assumed for HDR frames without mastering metadata
+const DEFAULT_PEAK_LUMINANCE: f32 = 1000.0;
+
+/// Rendering settings which don't require the frame to be uploaded again
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Settings {
+    pub chroma_location: ChromaLocation,
+    pub chroma_filter: ChromaFilter,
+    pub mipmaps: bool,
+    pub buffering: Buffering,
+    pub conversion: Conversion,
+    pub field_order: FieldOrder,
+    pub deinterlace: Deinterlace,
+    pub matrix: MatrixCoefficients,
+    pub range: ColorRange,
+    pub transfer: TransferFunction,
+    pub peak_luminance: Option<f32>,
+    pub tone_mapping: ToneMapping,
+    pub primaries: ColorPrimaries,
+    pub gamut_mapping: GamutMapping,
+    pub adjustments: PictureAdjustments,
+    pub lut_strength: f32,
+    pub border: Border,
+    pub cross_fade: Option<Duration>,
+    pub post_uniforms: [[f32; 4]; 4],
+    pub chroma_key: Option<ChromaKey>,
+    pub privacy: Regions,
+}
+
+impl Settings {
+    /// Takes the metadata of a new frame
+    pub(crate) fn update(&mut self, yuv: &Yuv) {
+        self.chroma_location = yuv.chroma_location;
+        self.field_order = yuv.field_order;
+        self.matrix = yuv.matrix;
+        self.range = yuv.range;
+        self.transfer = yuv.transfer;
+        self.peak_luminance = yuv.peak_luminance;
+        self.primaries = yuv.primaries;
+    }
+
+    /// Whether frames need a mip chain, which blurred privacy regions are sampled from
+    pub(crate) fn needs_mipmaps(&self) -> bool {
+        self.mipmaps || self.privacy.blurs()
+    }
+
+    /// Peak luminance of the frame relative to SDR reference white
+    pub(crate) fn peak(&self) -> f32 {
+        if self.transfer.is_hdr() {
+            self.peak_luminance.unwrap_or(DEFAULT_PEAK_LUMINANCE) / REFERENCE_WHITE
+        } else {
+            1.0
+        }
+    }
+
+    /// Transfer function the frame is displayed with, SDR frames are passed through as they are
+    pub(crate) fn output_transfer(&self) -> TransferFunction {
+        if self.transfer.is_hdr() || self.transfer == TransferFunction::Linear {
+            TransferFunction::Srgb
+        } else {
+            self.transfer
+        }
+    }
+}
+
+impl<Message> shader::Program<Message> for Program {
+    type State = ();
+    type Primitive = Primitive;
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        _cursor: mouse::Cursor,
+        bounds: Rectangle,
+    ) -> Self::Primitive {
+        Primitive {
+            frame: self.frame.clone(),
+            bounds,
+            settings: self.settings,
+            lut: self.lut.clone(),
+            mask: self.mask.clone(),
+            overlays: self.overlays.clone(),
+            opacity: self.opacity,
+            post: self.post.clone(),
+            screenshots: Mutex::new(self.screenshots.take()),
+            uploaded: self.uploaded.clone(),
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Primitive {
+    frame: Frame,
+    bounds: Rectangle,
+    settings: Settings,
+    lut: Option<Arc<Lut>>,
+    mask: Option<Mask>,
+    overlays: Vec<Overlay>,
+    opacity: f32,
+    post: PostProcess,
+    screenshots: Mutex<Vec<Request>>,
+    uploaded: Arc<AtomicU64>,
+}
+
 impl shader::Primitive for Primitive {
     fn prepare(
         &self,
@@ -111,65 +699,113 @@ impl shader::Primitive for Primitive {
         scale_factor: f32,
         storage: &mut shader::Storage,
     ) {
-        let Ok(mut state) = self.0.lock() else {
-            return;
-        };
+        let Frame {
+            version,
+            yuv,
+            dirty,
+        } = &self.frame;
 
         let size = Size::from(bounds.size());
         let target_size = Size::from((target_size.width as f32, target_size.height as f32)) / scale_factor;
 
-        match state.deref() {
-            State::Pending { yuv, .. } => {
-                if !storage.has::<Pipeline>() {
-                    storage.store(Pipeline::new(
-                        device,
-                        format,
-                        yuv.dimensions(),
-                        bounds,
-                        target_size,
-                        scale_factor,
-                    ));
-                }
-
-                let pipeline = storage.get_mut::<Pipeline>().expect("yuv pipeline");
-
-                pipeline.update_uniforms(
-                    queue,
-                    &Uniforms::new(
-                        size,
-                        yuv.dimensions().into(),
-                        yuv.downsampling_factor(),
-                        target_size,
-                    ),
-                );
-                pipeline.update_frame(queue, yuv);
-                pipeline.update_vertices(queue, bounds, target_size, scale_factor)
-            }
-
-            State::Prepared {
+        if !storage.has::<Pipeline>() {
+            storage.store(Pipeline::new(
+                device,
+                format,
+                yuv,
+                &self.settings,
+                &self.post,
+            ));
+        }
+
+        let pipeline = storage.get_mut::<Pipeline>().expect("yuv pipeline");
+        pipeline.update_post(device, &self.post);
+        pipeline.update_frames(device, yuv, &self.settings);
+
+        // frames are uploaded once, however often they are drawn
+        pipeline.complete_upload(device);
+        if !pipeline.has_frame(*version) && !yuv.y().is_empty() {
+            pipeline.update_frame(device, queue, yuv, *version, dirty.as_ref());
+        }
+
+        if pipeline.displays(*version) {
+            self.uploaded.fetch_max(*version, Ordering::AcqRel);
+        }
+
+        pipeline.update_lut(device, queue, self.lut.as_ref());
+        pipeline.update_mask(device, queue, self.mask.as_ref());
+
+        let image_dimensions = Size::from(yuv.dimensions());
+        let sampling_factor = yuv.downsampling_factor();
+
+        pipeline.update_conversion(device, queue, self.settings.conversion, |format| {
+            Uniforms::new(
+                image_dimensions,
                 image_dimensions,
                 sampling_factor,
-                ..
-            } => {
-                let pipeline = storage.get_mut::<Pipeline>().expect("yuv pipeline");
+                image_dimensions,
+                format,
+                &self.settings,
+            )
+        });
+        let fade = self
+            .settings
+            .cross_fade
+            .map_or(1.0, |duration| pipeline.fade(duration));
 
-                pipeline.update_uniforms(
-                    queue,
-                    &Uniforms::new(
-                        size,
-                        (*image_dimensions).into(),
-                        *sampling_factor,
-                        target_size,
-                    ),
-                );
-                pipeline.update_vertices(queue, bounds, target_size, scale_factor);
-            }
-        }
+        pipeline.update_uniforms(
+            queue,
+            &Uniforms::new(
+                size,
+                image_dimensions,
+                sampling_factor,
+                target_size,
+                format,
+                &self.settings,
+            )
+            .shape(
+                bounds,
+                scale_factor,
+                self.settings.border,
+                self.mask.is_some(),
+            )
+            .transition(self.opacity, fade),
+        );
+        pipeline.update_vertices(queue, bounds, target_size, scale_factor);
+        pipeline.update_overlays(
+            device,
+            queue,
+            &self.overlays,
+            bounds,
+            image_dimensions,
+            target_size,
+        );
+
+        let requests = self
+            .screenshots
+            .lock()
+            .map(|mut requests| std::mem::take(&mut *requests))
+            .unwrap_or_default();
+
+        for request in requests {
+            let dimensions = match request.resolution {
+                Resolution::Source => yuv.dimensions(),
+                Resolution::Display => Size {
+                    width: (bounds.width * scale_factor).round() as u32,
+                    height: (bounds.height * scale_factor).round() as u32,
+                },
+            };
 
-        *state = State::Prepared {
-            bounds: state.bounds(),
-            image_dimensions: state.image_dimensions(),
-            sampling_factor: state.sampling_factor(),
+            let uniforms = Uniforms::new(
+                dimensions.into(),
+                image_dimensions,
+                sampling_factor,
+                dimensions.into(),
+                READBACK_FORMAT,
+                &self.settings,
+            );
+
+            pipeline.screenshot(device, queue, dimensions, &uniforms, request);
         }
     }
 
@@ -183,8 +819,114 @@ impl shader::Primitive for Primitive {
     ) {
         let pipeline = storage.get::<Pipeline>().unwrap();
 
-        if let Ok(state) = self.0.lock() {
-            pipeline.render(target, encoder, state.bounds());
+        pipeline.render(target, encoder, self.bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Data, Format};
+
+    fn yuv(data: Data) -> Yuv {
+        Yuv {
+            format: Format::Y444,
+            data,
+            dimensions: Size {
+                width: 2,
+                height: 2,
+            },
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn shared_frames_are_uploaded_once() {
+        let data = Data::from(vec![0; 12]);
+        let mut program = Program::new(yuv(data.clone()));
+        let version = program.frame.version;
+
+        program.update_frame(yuv(data.clone()));
+        assert_eq!(program.frame.version, version);
+
+        program.update_frame(yuv(Data::from(data.to_vec())));
+        assert_ne!(program.frame.version, version);
+    }
+
+    #[test]
+    fn frames_sharing_a_slice_are_uploaded_once() {
+        let bytes: Arc<[u8]> = vec![0; 12].into();
+        let mut program = Program::new(yuv(bytes.clone().into()));
+        let version = program.frame.version;
+
+        program.update_frame(yuv(bytes.clone().into()));
+        assert_eq!(program.frame.version, version);
+
+        program.update_frame(yuv(Arc::<[u8]>::from(bytes.to_vec()).into()));
+        assert_ne!(program.frame.version, version);
+    }
+
+    #[test]
+    fn frames_of_the_same_sequence_are_uploaded_once() {
+        let sequenced = |sequence| Yuv {
+            sequence: Some(sequence),
+            ..yuv(Data::from(vec![0; 12]))
+        };
+
+        let mut program = Program::new(sequenced(1));
+        let version = program.frame.version;
+
+        program.update_frame(sequenced(1));
+        assert_eq!(program.frame.version, version);
+
+        program.update_frame(sequenced(2));
+        assert!(program.frame.version > version);
+        assert!(!program.is_frame_uploaded());
+    }
+
+    #[test]
+    fn masks_larger_than_their_data_are_invalid() {
+        let mask = |width, height| Mask {
+            alpha: Data::from(vec![0; 16]),
+            dimensions: Size { width, height },
+        };
+
+        assert!(mask(4, 4).is_valid());
+        assert!(!mask(4, 5).is_valid());
+        assert!(!mask(u32::MAX, u32::MAX).is_valid());
+    }
+
+    #[test]
+    fn overlays_larger_than_their_data_are_invalid() {
+        let overlay = |width, height| Overlay {
+            rgba: Data::from(vec![0; 64]),
+            dimensions: Size { width, height },
+            bounds: Rectangle::default(),
+        };
+
+        assert!(overlay(4, 4).is_valid());
+        assert!(!overlay(4, 5).is_valid());
+        assert!(!overlay(u32::MAX, u32::MAX).is_valid());
+    }
+
+    #[test]
+    fn images_are_converted_again_with_new_settings() {
+        let mut program = Program::new(yuv(Data::from(vec![0; 12])));
+        let converted = |program: &Program| {
+            program.image();
+            program
+                .image
+                .borrow()
+                .as_ref()
+                .map(|(_, settings, _)| *settings)
+        };
+
+        assert_eq!(converted(&program), Some(program.settings));
+
+        program.update_adjustments(PictureAdjustments {
+            brightness: 0.1,
+            ..Default::default()
+        });
+        assert_eq!(converted(&program), Some(program.settings));
+    }
 }
@@ -0,0 +1,122 @@
+use std::sync::atomic::Ordering;
+
+use iced::widget::shader::wgpu;
+use iced::Rectangle;
+
+use super::pipeline::{self, Pipeline, Uniforms, READBACK_FORMAT};
+use super::screenshot::Request;
+use super::{Program, Resolution, Snapshot};
+use crate::Size;
+
+/// Converts the frames of [`Program`]s to RGBA without a window, for tests and batch
+/// processing
+///
+/// Any wgpu device works, including software adapters like llvmpipe or lavapipe.
+pub struct Converter<'a> {
+    device: &'a wgpu::Device,
+    queue: &'a wgpu::Queue,
+    pipeline: Option<Pipeline>,
+}
+
+impl<'a> Converter<'a> {
+    pub fn new(device: &'a wgpu::Device, queue: &'a wgpu::Queue) -> Self {
+        Self {
+            device,
+            queue,
+            pipeline: None,
+        }
+    }
+
+    /// Renders the latest frame of the [`Program`] with its current settings into a new
+    /// `Rgba8Unorm` texture with the dimensions of the frame. The frame is only uploaded
+    /// if it isn't the one converted last.
+    ///
+    /// Corners, border and [`Mask`] apply as if the frame was drawn at its size with a scale
+    /// factor of 1.0, over opaque black. So do opacity and cross-fades, which progress with
+    /// the time since the previous frame was converted.
+    ///
+    /// [`Mask`]: crate::Mask
+    ///
+    /// Returns `None` if the [`Program`] has no frame.
+    pub fn texture(&mut self, program: &Program) -> Option<wgpu::Texture> {
+        let yuv = &program.frame.yuv;
+        let version = program.frame.version;
+        let dimensions = yuv.dimensions();
+
+        if dimensions.width == 0 || dimensions.height == 0 || yuv.y().is_empty() {
+            return None;
+        }
+
+        let size = Size::from(dimensions);
+
+        let pipeline = match &mut self.pipeline {
+            Some(pipeline) => {
+                pipeline.update_post(self.device, &program.post);
+                pipeline.update_frames(self.device, yuv, &program.settings);
+                pipeline
+            }
+            None => self.pipeline.insert(Pipeline::new(
+                self.device,
+                READBACK_FORMAT,
+                yuv,
+                &program.settings,
+                &program.post,
+            )),
+        };
+
+        pipeline.update_lut(self.device, self.queue, program.lut.as_ref());
+        pipeline.update_mask(self.device, self.queue, program.mask.as_ref());
+
+        if !pipeline.has_frame(version) {
+            pipeline.update_frame(
+                self.device,
+                self.queue,
+                yuv,
+                version,
+                program.frame.dirty.as_ref(),
+            );
+        }
+        pipeline.finish_upload(self.device);
+        program.uploaded.fetch_max(version, Ordering::AcqRel);
+
+        let uniforms = Uniforms::new(
+            size,
+            size,
+            yuv.downsampling_factor(),
+            size,
+            READBACK_FORMAT,
+            &program.settings,
+        )
+        .shape(
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: size.width,
+                height: size.height,
+            },
+            1.0,
+            program.settings.border,
+            program.mask.is_some(),
+        );
+
+        let fade = program
+            .settings
+            .cross_fade
+            .map_or(1.0, |duration| pipeline.fade(duration));
+        let uniforms = uniforms.transition(program.opacity, fade);
+
+        Some(pipeline.render_offscreen(self.device, self.queue, dimensions, &uniforms))
+    }
+
+    /// Renders the latest frame of the [`Program`] like [`Converter::texture`] and reads it
+    /// back, blocking until the GPU is done.
+    pub fn rgba(&mut self, program: &Program) -> Option<Snapshot> {
+        let texture = self.texture(program)?;
+        let (request, screenshot) = Request::new(Resolution::Source);
+
+        pipeline::read(self.device, self.queue, &texture, request);
+        self.device.poll(wgpu::Maintain::Wait);
+
+        screenshot.ready()
+    }
+}
@@ -0,0 +1,28 @@
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+// fraction of the layer covered by its plane
+@group(0) @binding(2) var<uniform> region: vec2<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // a single triangle covering the whole target
+    out.uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.position = vec4<f32>(out.uv.x * 2.0 - 1.0, 1.0 - out.uv.y * 2.0, 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // replicate the edge of the plane instead of averaging in the unused part of the layer
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(source));
+
+    return textureSample(source, source_sampler, min(input.uv, region - half_texel));
+}
@@ -1,36 +1,88 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use iced::time::Instant;
 use iced::widget::shader::wgpu;
-use iced::{
-    widget::shader::wgpu::util::{BufferInitDescriptor, DeviceExt},
-    Rectangle,
-};
+use iced::Rectangle;
 
+mod compute;
 mod instance;
+mod lut;
+mod mask;
+mod mipmaps;
+mod overlay;
+mod readback;
+mod staging;
 mod uniforms;
+mod wall;
 
-use super::Renderable;
+use super::screenshot::Request;
+use super::{Buffering, Conversion, Deinterlace, Dirty, Mask, Overlay, Renderable, Settings};
 use crate::yuv::Size;
+use crate::PostProcess;
+use compute::Compute;
 use instance::Instance;
+use lut::Lut;
+use mipmaps::Mipmaps;
+use overlay::Overlays;
+use readback::Readback;
+pub use readback::{read, FORMAT as READBACK_FORMAT};
+use staging::Staging;
 pub use uniforms::Uniforms;
+pub use wall::{Tile, Wall};
 
 pub struct Pipeline {
+    format: wgpu::TextureFormat,
     pipeline: wgpu::RenderPipeline,
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Hook compiled into the shader
+    post: PostProcess,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
     uniforms_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
-    texture: wgpu::Texture,
-    texture_bind_group: wgpu::BindGroup,
+    frames: Vec<Frame>,
+    texture_bind_groups: Vec<wgpu::BindGroup>,
+    /// Frame which is displayed
+    current: usize,
+    /// When the displayed frame was first shown
+    shown: Instant,
+    /// Frame whose staged upload is in flight, displayed once it completes
+    pending: Option<usize>,
+    staged: bool,
+    /// Whether the frame before the displayed one is read, so uploads skip it
+    keeps_previous: bool,
+    /// Whether any staged upload completed yet
+    uploaded: bool,
+    compute: Option<Compute>,
+    lut: Lut,
+    /// Alpha mask bound with the uniforms, and its texture
+    mask: Option<Mask>,
+    mask_view: wgpu::TextureView,
+    /// Created once there are overlays
+    overlays: Option<Overlays>,
     vertex_buffer: wgpu::Buffer,
     scale_factor: f32,
+    readback: Option<Readback>,
 }
 
 impl Pipeline {
     pub fn new(
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
-        image_dimensions: Size<u32>,
-        bounds: Rectangle,
-        target_size: Size,
-        scale_factor: f32,
+        yuv: &Renderable,
+        settings: &Settings,
+        post: &PostProcess,
     ) -> Self {
+        // frames are only converted by compute passes where they are supported
+        let stages = if Compute::is_supported(device) {
+            wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE
+        } else {
+            wgpu::ShaderStages::FRAGMENT
+        };
+
         let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("yuv uniform buffer"),
             size: std::mem::size_of::<Uniforms>() as u64,
@@ -38,254 +90,476 @@ impl Pipeline {
             mapped_at_creation: false,
         });
 
-        let uniform_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("yuv uniform bind group layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(
-                            std::mem::size_of::<Uniforms>() as u64
-                        ),
-                    },
-                    count: None,
-                }],
-            });
-
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("yuv uniform bind group"),
-            layout: &uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                    buffer: &uniforms_buffer,
-                    offset: 0,
-                    size: None,
-                }),
-            }],
-        });
+        let uniform_bind_group_layout = uniform_bind_group_layout(device, stages);
+        let mask_view = mask::empty(device);
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("yuv texture"),
-            size: wgpu::Extent3d {
-                width: image_dimensions.width,
-                height: image_dimensions.height,
-                depth_or_array_layers: 3,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::COPY_SRC
-                | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+        let uniform_bind_group = uniform_bind_group(
+            device,
+            "yuv uniform bind group",
+            &uniform_bind_group_layout,
+            &uniforms_buffer,
+            &mask_view,
+        );
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
-            dimension: Some(wgpu::TextureViewDimension::D2Array),
-            ..Default::default()
-        });
+        let sampler = sampler(device);
+        let texture_bind_group_layout = texture_bind_group_layout(device, stages);
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("yuv sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        let (frames, texture_bind_groups) = frames(
+            device,
+            &texture_bind_group_layout,
+            &sampler,
+            yuv,
+            frame_count(settings),
+            settings.needs_mipmaps(),
+        );
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("yuv texture bind group layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
-
-        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-            label: Some("texture bind group"),
-        });
+        let lut = Lut::new(device, stages);
 
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("yuv pipeline layout"),
-            bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &texture_bind_group_layout,
+                lut.bind_group_layout(),
+            ],
             push_constant_ranges: &[],
         });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("yuv shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(concat!(include_str!(
-                "shader.wgsl"
-            ),))),
-        });
+        let shader = shader(device, post);
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("iced_wgpu::image pipeline"),
-            layout: Some(&layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Instance::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::One,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                front_face: wgpu::FrontFace::Cw,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let pipeline = render_pipeline(device, &layout, &shader, format, FRAME);
 
-        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        // written with the bounds of the widget before each draw
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("yuv vertex buffer"),
+            size: std::mem::size_of::<Instance>() as u64,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            contents: bytemuck::cast_slice(&Instance::frame(bounds, target_size)),
+            mapped_at_creation: false,
         });
 
         Self {
+            format,
             pipeline,
+            layout,
+            shader,
+            post: post.clone(),
+            uniform_bind_group_layout,
+            texture_bind_group_layout,
+            sampler,
             uniforms_buffer,
             uniform_bind_group,
-            texture,
-            texture_bind_group,
+            frames,
+            texture_bind_groups,
+            current: 0,
+            shown: Instant::now(),
+            pending: None,
+            staged: settings.buffering != Buffering::Single,
+            keeps_previous: keeps_previous(settings),
+            uploaded: false,
+            compute: None,
+            lut,
+            mask: None,
+            mask_view,
+            overlays: None,
             vertex_buffer,
-            scale_factor,
+            scale_factor: 1.0,
+            readback: None,
         }
     }
 
-    pub fn update_uniforms(&mut self, queue: &wgpu::Queue, uniforms: &Uniforms) {
-        queue.write_buffer(&self.uniforms_buffer, 0, bytemuck::bytes_of(uniforms));
+    /// Creates the frames again if they can't hold `yuv`, or lack the mip chain, the
+    /// number of frames or the staging the `settings` ask for, since the pipeline is shared
+    /// by every [`Program`] drawn
+    ///
+    /// [`Program`]: crate::Program
+    pub fn update_frames(&mut self, device: &wgpu::Device, yuv: &Renderable, settings: &Settings) {
+        let staged = settings.buffering != Buffering::Single;
+        let count = frame_count(settings);
+        self.keeps_previous = keeps_previous(settings);
+
+        if yuv.y().is_empty()
+            || (self.staged == staged
+                && self.frames.len() >= count
+                && self
+                    .frames
+                    .iter()
+                    .all(|frame| frame.fits(yuv, settings.needs_mipmaps())))
+        {
+            return;
+        }
+
+        (self.frames, self.texture_bind_groups) = frames(
+            device,
+            &self.texture_bind_group_layout,
+            &self.sampler,
+            yuv,
+            self.frames.len().max(count),
+            settings.needs_mipmaps(),
+        );
+        self.staged = staged;
+
+        // the new frames are empty, so the frame is uploaded again before it is shown
+        self.current = 0;
+        self.pending = None;
+        self.uploaded = false;
+        self.compute = None;
     }
 
-    pub fn update_frame(&mut self, queue: &wgpu::Queue, yuv: &Renderable) {
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
-                aspect: wgpu::TextureAspect::default(),
-            },
-            yuv.y(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(yuv.y().len() as u32 / yuv.dimensions().height),
-                rows_per_image: Some(yuv.dimensions().height),
-            },
-            wgpu::Extent3d {
-                width: yuv.dimensions().width,
-                height: yuv.dimensions().height,
-                depth_or_array_layers: 1,
-            },
+    /// Compiles the shader again with `post` unless it is compiled with it already, since
+    /// the pipeline is shared by every [`Program`] drawn
+    ///
+    /// [`Program`]: crate::Program
+    pub fn update_post(&mut self, device: &wgpu::Device, post: &PostProcess) {
+        if self.post == *post {
+            return;
+        }
+
+        self.shader = shader(device, post);
+        self.pipeline = render_pipeline(device, &self.layout, &self.shader, self.format, FRAME);
+        self.post = post.clone();
+
+        // created again with the new shader when they are needed
+        self.compute = None;
+        self.overlays = None;
+        self.readback = None;
+    }
+
+    pub fn update_uniforms(&mut self, queue: &wgpu::Queue, uniforms: &Uniforms) {
+        let decode = self
+            .compute
+            .as_ref()
+            .is_some_and(|compute| compute.decodes(self.format));
+
+        queue.write_buffer(
+            &self.uniforms_buffer,
+            0,
+            bytemuck::bytes_of(&uniforms.decode(decode)),
         );
+    }
 
-        let downsampled_width = yuv.dimensions().width / yuv.downsampling_factor() as u32;
-        let downsampled_height = yuv.dimensions().height / yuv.downsampling_factor() as u32;
+    /// Converts the displayed frame with a compute pass if the [`Conversion`] asks for it
+    /// and it wasn't converted yet, `uniforms` gives the uniforms for a target format
+    pub fn update_conversion(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        conversion: Conversion,
+        uniforms: impl Fn(wgpu::TextureFormat) -> Uniforms,
+    ) {
+        let format = Compute::format(conversion).filter(|_| Compute::is_supported(device));
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d { x: 0, y: 0, z: 1 },
-                aspect: wgpu::TextureAspect::default(),
-            },
-            yuv.u(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(
-                    yuv.y().len() as u32
-                        / yuv.dimensions().height
-                        / yuv.downsampling_factor() as u32,
-                ),
-                rows_per_image: Some(downsampled_height),
-            },
-            wgpu::Extent3d {
-                width: downsampled_width,
-                height: downsampled_height,
-                depth_or_array_layers: 1,
-            },
+        let Some(format) = format else {
+            self.compute = None;
+            return;
+        };
+
+        if self.compute.as_ref().map(Compute::texture_format) != Some(format) {
+            let texture = &self.frames[self.current].texture;
+
+            self.compute = Some(Compute::new(
+                device,
+                compute::Shared {
+                    shader: &self.shader,
+                    uniform_bind_group_layout: &self.uniform_bind_group_layout,
+                    texture_bind_group_layout: &self.texture_bind_group_layout,
+                    lut_bind_group_layout: self.lut.bind_group_layout(),
+                },
+                format,
+                self.format,
+                wgpu::Extent3d {
+                    width: texture.width(),
+                    height: texture.height(),
+                    depth_or_array_layers: 1,
+                },
+            ));
+        }
+
+        let Some(compute) = &mut self.compute else {
+            return;
+        };
+
+        // frames encoded for SDR display are decoded when drawn, others are converted for
+        // the target as they would be when drawn
+        let uniforms = uniforms(match format {
+            wgpu::TextureFormat::Rgba8Unorm => format,
+            _ => self.format,
+        });
+
+        compute.convert(
+            device,
+            queue,
+            self.frames[self.current].version,
+            &uniforms,
+            &self.texture_bind_groups[self.current],
+            self.lut.bind_group(),
         );
+    }
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d { x: 0, y: 0, z: 2 },
-                aspect: wgpu::TextureAspect::default(),
-            },
-            yuv.v(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(
-                    yuv.y().len() as u32
-                        / yuv.dimensions().height
-                        / yuv.downsampling_factor() as u32,
-                ),
-                rows_per_image: Some(downsampled_height),
-            },
-            wgpu::Extent3d {
-                width: downsampled_width,
-                height: downsampled_height,
-                depth_or_array_layers: 1,
-            },
+    /// Whether the frame of `version` is displayed or its upload is in flight
+    pub fn has_frame(&self, version: u64) -> bool {
+        self.displays(version)
+            || self
+                .pending
+                .is_some_and(|pending| self.frames[pending].version == version)
+    }
+
+    /// Whether the frame of `version` is displayed
+    pub fn displays(&self, version: u64) -> bool {
+        self.frames[self.current].version == version
+    }
+
+    /// Uploads the frame of `version`, which is displayed once the upload completed
+    ///
+    /// Only the `dirty` regions are written if the texture written to holds the frame
+    /// they apply to, which is only the case without staged uploads.
+    pub fn update_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        yuv: &Renderable,
+        version: u64,
+        dirty: Option<&Dirty>,
+    ) {
+        let planes = planes(yuv);
+
+        if !self.staged {
+            self.current = (self.current + 1) % self.frames.len();
+            self.shown = Instant::now();
+            let frame = &mut self.frames[self.current];
+
+            let regions = dirty
+                .filter(|dirty| dirty.base == frame.version)
+                .map(|dirty| &dirty.regions[..]);
+
+            for (layer, plane) in planes.iter().enumerate() {
+                match regions {
+                    Some(regions) => {
+                        for region in regions {
+                            plane.write(queue, &frame.texture, layer as u32, Some(*region));
+                        }
+                    }
+                    None => plane.write(queue, &frame.texture, layer as u32, None),
+                }
+            }
+
+            frame.version = version;
+
+            if let Some(mipmaps) = &frame.mipmaps {
+                mipmaps.generate(device, queue);
+            }
+
+            return;
+        }
+
+        self.complete_upload(device);
+
+        // a frame is skipped while the others are busy, and the latest one is uploaded
+        // once one is free
+        let Some(next) = next_upload(
+            self.frames.len(),
+            self.current,
+            self.pending,
+            self.keeps_previous,
+        ) else {
+            return;
+        };
+
+        let frame = &mut self.frames[next];
+
+        let staging = match frame.staging.take() {
+            // the GPU is still copying the previous upload from the buffer
+            Some(staging) if !staging.is_mapped() => {
+                frame.staging = Some(staging);
+                return;
+            }
+            Some(staging) if staging.fits(&planes) => staging,
+            _ => Staging::new(device, &planes),
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("yuv upload encoder"),
+        });
+
+        staging.upload(&mut encoder, &frame.texture, &planes);
+        queue.submit(Some(encoder.finish()));
+        staging.recycle();
+
+        if let Some(mipmaps) = &frame.mipmaps {
+            mipmaps.generate(device, queue);
+        }
+
+        frame.staging = Some(staging);
+        frame.version = version;
+        self.pending = Some(next);
+    }
+
+    /// Displays the frame of the staged upload in flight if it is done
+    pub fn complete_upload(&mut self, device: &wgpu::Device) {
+        let Some(pending) = self.pending else {
+            return;
+        };
+
+        device.poll(wgpu::Maintain::Poll);
+
+        if self.frames[pending]
+            .staging
+            .as_ref()
+            .is_some_and(Staging::is_mapped)
+        {
+            self.current = pending;
+            self.shown = Instant::now();
+            self.pending = None;
+            self.uploaded = true;
+        }
+    }
+
+    /// Waits for the staged upload in flight, if any, and displays its frame
+    pub fn finish_upload(&mut self, device: &wgpu::Device) {
+        if let Some(pending) = self.pending.take() {
+            device.poll(wgpu::Maintain::Wait);
+
+            self.current = pending;
+            self.shown = Instant::now();
+            self.uploaded = true;
+        }
+    }
+
+    /// Progress of the cross-fade from the previous frame to the displayed one over
+    /// `duration`, 1.0 once it is done or if there is no previous frame
+    pub fn fade(&self, duration: Duration) -> f32 {
+        let previous = (self.current + self.frames.len() - 1) % self.frames.len();
+
+        if previous == self.current || self.frames[previous].version == 0 || duration.is_zero() {
+            return 1.0;
+        }
+
+        (self.shown.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0)
+    }
+
+    /// Binds the tables of the LUT unless they are bound already
+    pub fn update_lut(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: Option<&Arc<crate::Lut>>,
+    ) {
+        if !self.lut.upload(device, queue, lut) {
+            return;
+        }
+
+        if let Some(compute) = &mut self.compute {
+            compute.invalidate();
+        }
+    }
+
+    /// Binds the alpha mask unless it is bound already
+    pub fn update_mask(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, mask: Option<&Mask>) {
+        let bound = match (&self.mask, mask) {
+            (Some(bound), Some(mask)) => {
+                bound.alpha.ptr_eq(&mask.alpha) && bound.dimensions == mask.dimensions
+            }
+            (bound, mask) => bound.is_none() && mask.is_none(),
+        };
+
+        if bound {
+            return;
+        }
+
+        self.mask_view = match mask {
+            Some(mask) => mask::texture(device, queue, mask),
+            None => mask::empty(device),
+        };
+
+        self.uniform_bind_group = uniform_bind_group(
+            device,
+            "yuv uniform bind group",
+            &self.uniform_bind_group_layout,
+            &self.uniforms_buffer,
+            &self.mask_view,
         );
+        self.mask = mask.cloned();
+
+        if let Some(readback) = &mut self.readback {
+            readback.update_mask(device, &self.uniform_bind_group_layout, &self.mask_view);
+        }
+    }
+
+    /// Sets the [`Overlay`]s drawn over the frame within `bounds`
+    pub fn update_overlays(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        overlays: &[Overlay],
+        bounds: Rectangle,
+        image_dimensions: Size,
+        target_size: Size,
+    ) {
+        if overlays.is_empty() && self.overlays.is_none() {
+            return;
+        }
+
+        self.overlays
+            .get_or_insert_with(|| {
+                Overlays::new(
+                    device,
+                    self.format,
+                    &self.shader,
+                    &self.uniform_bind_group_layout,
+                )
+            })
+            .update(
+                device,
+                queue,
+                overlays,
+                bounds,
+                image_dimensions,
+                target_size,
+            );
+    }
+
+    /// Renders the current frame into a [`READBACK_FORMAT`] texture and reads it back
+    pub fn screenshot(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: Size<u32>,
+        uniforms: &Uniforms,
+        request: Request,
+    ) {
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return;
+        }
+
+        let texture = self.render_offscreen(device, queue, dimensions, uniforms);
+
+        read(device, queue, &texture, request);
+    }
+
+    /// Renders the current frame into a new [`READBACK_FORMAT`] texture of `dimensions`
+    pub fn render_offscreen(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: Size<u32>,
+        uniforms: &Uniforms,
+    ) -> wgpu::Texture {
+        let readback = self.readback.get_or_insert_with(|| {
+            Readback::new(
+                device,
+                render_pipeline(device, &self.layout, &self.shader, READBACK_FORMAT, FRAME),
+                &self.uniform_bind_group_layout,
+                &self.mask_view,
+            )
+        });
+
+        readback.render(
+            device,
+            queue,
+            dimensions,
+            uniforms,
+            &self.texture_bind_groups[self.current],
+            self.lut.bind_group(),
+        )
     }
 
     pub fn update_vertices(
@@ -309,6 +583,11 @@ impl Pipeline {
         encoder: &mut wgpu::CommandEncoder,
         bounds: Rectangle,
     ) {
+        // nothing is drawn until the first upload completed
+        if self.frames[self.current].version == 0 {
+            return;
+        }
+
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("yuv.pipeline.pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -332,9 +611,483 @@ impl Pipeline {
         );
 
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-        pass.set_bind_group(1, &self.texture_bind_group, &[]);
+
+        let textures = &self.texture_bind_groups[self.current];
+
+        if let Some(compute) = &self.compute {
+            compute.bind(
+                &mut pass,
+                &self.uniform_bind_group,
+                textures,
+                self.lut.bind_group(),
+            );
+        } else {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            pass.set_bind_group(1, textures, &[]);
+            pass.set_bind_group(2, self.lut.bind_group(), &[]);
+        }
+
         pass.draw(0..6, 0..1);
+
+        if let Some(overlays) = &self.overlays {
+            overlays.render(&mut pass, &self.uniform_bind_group);
+        }
+    }
+}
+
+/// Bytes of a plane of a frame and the region of its layer
+pub struct Plane<'a> {
+    data: &'a [u8],
+    bytes_per_row: u32,
+    extent: wgpu::Extent3d,
+    /// Subsampling of the plane relative to luma
+    factor: u32,
+}
+
+impl<'a> Plane<'a> {
+    /// Writes `region` of the plane, given in luma samples, or all of it to `layer`
+    fn write(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        layer: u32,
+        region: Option<Rectangle<u32>>,
+    ) {
+        let Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = region.unwrap_or(Rectangle {
+            x: 0,
+            y: 0,
+            width: self.extent.width * self.factor,
+            height: self.extent.height * self.factor,
+        });
+
+        // chroma samples partially covered by the region are written as well
+        let left = (x / self.factor).min(self.extent.width);
+        let top = (y / self.factor).min(self.extent.height);
+        let right = x
+            .saturating_add(width)
+            .div_ceil(self.factor)
+            .min(self.extent.width);
+        let bottom = y
+            .saturating_add(height)
+            .div_ceil(self.factor)
+            .min(self.extent.height);
+
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: left,
+                    y: top,
+                    z: layer,
+                },
+                aspect: wgpu::TextureAspect::default(),
+            },
+            self.data,
+            wgpu::ImageDataLayout {
+                offset: (top * self.bytes_per_row + left) as u64,
+                bytes_per_row: Some(self.bytes_per_row),
+                rows_per_image: Some(self.extent.height),
+            },
+            wgpu::Extent3d {
+                width: right - left,
+                height: bottom - top,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+fn planes(yuv: &Renderable) -> [Plane<'_>; 3] {
+    let dimensions = yuv.dimensions();
+    let factor = yuv.downsampling_factor() as u32;
+    let bytes_per_row = yuv.y().len() as u32 / dimensions.height;
+
+    let chroma = |data| Plane {
+        data,
+        bytes_per_row: bytes_per_row / factor,
+        extent: wgpu::Extent3d {
+            width: dimensions.width / factor,
+            height: dimensions.height / factor,
+            depth_or_array_layers: 1,
+        },
+        factor,
+    };
+
+    [
+        Plane {
+            data: yuv.y(),
+            bytes_per_row,
+            extent: wgpu::Extent3d {
+                width: dimensions.width,
+                height: dimensions.height,
+                depth_or_array_layers: 1,
+            },
+            factor: 1,
+        },
+        chroma(yuv.u()),
+        chroma(yuv.v()),
+    ]
+}
+
+/// Number of frames the `settings` need
+fn frame_count(settings: &Settings) -> usize {
+    settings.buffering.frames() + usize::from(keeps_previous(settings))
+}
+
+/// Whether motion adaptive deinterlacing or cross-fades read the frame before the
+/// displayed one, which uploads must not overwrite
+fn keeps_previous(settings: &Settings) -> bool {
+    settings.deinterlace == Deinterlace::Adaptive || settings.cross_fade.is_some()
+}
+
+/// Frame of the `len` frames which the next staged upload goes to, following the one in
+/// flight, or `None` if that is the displayed frame or, if `keep_previous`, the frame
+/// before it
+fn next_upload(
+    len: usize,
+    current: usize,
+    pending: Option<usize>,
+    keep_previous: bool,
+) -> Option<usize> {
+    let next = (pending.unwrap_or(current) + 1) % len;
+    let previous = (current + len - 1) % len;
+
+    (next != current && !(keep_previous && next == previous)).then_some(next)
+}
+
+/// Creates `count` frames for `yuv`, each bound alongside the frame uploaded before it
+fn frames(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    yuv: &Renderable,
+    count: usize,
+    mipmaps: bool,
+) -> (Vec<Frame>, Vec<wgpu::BindGroup>) {
+    let frames: Vec<_> = (0..count)
+        .map(|_| Frame::new(device, yuv, mipmaps))
+        .collect();
+
+    let texture_views: Vec<_> = frames
+        .iter()
+        .map(|frame| {
+            frame.texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let bind_groups = (0..count)
+        .map(|i| {
+            let previous = (i + count - 1) % count;
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture_views[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&texture_views[previous]),
+                    },
+                ],
+                label: Some("texture bind group"),
+            })
+        })
+        .collect();
+
+    (frames, bind_groups)
+}
+
+/// Texture holding the planes of an uploaded frame as layers
+struct Frame {
+    texture: wgpu::Texture,
+    /// Subsampling of the chroma planes, which the mip chain is generated for
+    downsampling_factor: f32,
+    mipmaps: Option<Mipmaps>,
+    /// Buffer staging uploads to the texture, if they are staged
+    staging: Option<Staging>,
+    /// Version of the frame uploaded to the texture, 0 for none
+    version: u64,
+}
+
+impl Frame {
+    fn new(device: &wgpu::Device, yuv: &Renderable, mipmaps: bool) -> Self {
+        let image_dimensions = yuv.dimensions();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("yuv texture"),
+            size: wgpu::Extent3d {
+                width: image_dimensions.width,
+                height: image_dimensions.height,
+                depth_or_array_layers: 3,
+            },
+            mip_level_count: if mipmaps {
+                Mipmaps::level_count(image_dimensions.width, image_dimensions.height)
+            } else {
+                1
+            },
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: if mipmaps {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::empty()
+            } | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let mipmaps = mipmaps.then(|| {
+            let chroma_region = 1.0 / yuv.downsampling_factor();
+
+            Mipmaps::new(device, &texture, [1.0, chroma_region, chroma_region])
+        });
+
+        Self {
+            texture,
+            downsampling_factor: yuv.downsampling_factor(),
+            mipmaps,
+            staging: None,
+            version: 0,
+        }
+    }
+
+    /// Whether the texture can hold `yuv`, with a mip chain if `mipmaps` is set
+    ///
+    /// A mip chain which is no longer needed is kept, rather than creating the frames again
+    /// whenever blurred privacy regions come and go.
+    fn fits(&self, yuv: &Renderable, mipmaps: bool) -> bool {
+        let Size { width, height } = yuv.dimensions();
+
+        self.texture.width() == width
+            && self.texture.height() == height
+            && self.downsampling_factor == yuv.downsampling_factor()
+            && (self.mipmaps.is_some() || !mipmaps)
+    }
+}
+
+/// Entry points of a render pipeline and the instances drawn with it
+struct EntryPoints {
+    vertex: &'static str,
+    fragment: &'static str,
+    instance: fn() -> wgpu::VertexBufferLayout<'static>,
+}
+
+const FRAME: EntryPoints = EntryPoints {
+    vertex: "vs_main",
+    fragment: "fs_main",
+    instance: Instance::desc,
+};
+
+fn shader(device: &wgpu::Device, post: &PostProcess) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("yuv shader"),
+        source: wgpu::ShaderSource::Wgsl(post.shader().into()),
+    })
+}
+
+fn sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("yuv sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+/// Layout of the uniforms and the alpha mask applied to the frame
+fn uniform_bind_group_layout(
+    device: &wgpu::Device,
+    stages: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("yuv uniform bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | stages,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<Uniforms>() as u64),
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn uniform_bind_group(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::BindGroupLayout,
+    uniforms: &wgpu::Buffer,
+    mask: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniforms.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(mask),
+            },
+        ],
+    })
+}
+
+/// Layout of the planes of a frame, the sampler and the planes of the frame before it
+fn texture_bind_group_layout(
+    device: &wgpu::Device,
+    stages: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("yuv texture bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: stages,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: stages,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: stages,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    entry_points: EntryPoints,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("iced_wgpu::image pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: entry_points.vertex,
+            buffers: &[(entry_points.instance)()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: entry_points.fragment,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::SrcAlpha,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Cw,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_uploads_keep_the_previous_frame() {
+        let settings = Settings {
+            buffering: Buffering::Triple,
+            cross_fade: Some(Duration::from_millis(500)),
+            ..Settings::default()
+        };
+        let len = frame_count(&settings);
+        let keep_previous = keeps_previous(&settings);
+        assert_eq!(len, 4);
+
+        // frames are pushed while the upload of the first is still in flight, and the
+        // third would overwrite the frame the displayed one fades from
+        let first = next_upload(len, 0, None, keep_previous);
+        let second = next_upload(len, 0, first, keep_previous);
+        assert_eq!((first, second), (Some(1), Some(2)));
+        assert_eq!(next_upload(len, 0, second, keep_previous), None);
+        assert_eq!(next_upload(len, 0, second, false), Some(3));
+
+        // once the first upload is displayed, the frame before it is the one to keep
+        assert_eq!(next_upload(len, 1, second, keep_previous), Some(3));
+        assert_eq!(next_upload(len, 1, Some(3), keep_previous), None);
     }
 }
@@ -0,0 +1,265 @@
+use iced::widget::shader::wgpu;
+
+use super::Uniforms;
+use crate::program::Conversion;
+
+/// Converts each new frame once with a compute pass into an RGBA texture, which is then
+/// drawn instead of converting the frame on every draw
+pub struct Compute {
+    format: wgpu::TextureFormat,
+    dimensions: wgpu::Extent3d,
+    compute_pipeline: wgpu::ComputePipeline,
+    draw_pipeline: wgpu::RenderPipeline,
+    uniforms_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    storage_bind_group: wgpu::BindGroup,
+    texture_bind_group: wgpu::BindGroup,
+    /// Version of the frame and uniforms of the latest conversion
+    converted: Option<(u64, Uniforms)>,
+}
+
+/// Resources of the frame pipeline the passes share
+pub struct Shared<'a> {
+    pub shader: &'a wgpu::ShaderModule,
+    pub uniform_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub texture_bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub lut_bind_group_layout: &'a wgpu::BindGroupLayout,
+}
+
+impl Compute {
+    /// Whether the device runs compute shaders, which e.g. WebGL doesn't
+    pub fn is_supported(device: &wgpu::Device) -> bool {
+        device.limits().max_compute_invocations_per_workgroup >= 64
+    }
+
+    /// Format of the converted texture, `None` for conversion on every draw
+    pub fn format(conversion: Conversion) -> Option<wgpu::TextureFormat> {
+        match conversion {
+            Conversion::Fragment => None,
+            Conversion::Compute => Some(wgpu::TextureFormat::Rgba8Unorm),
+            Conversion::ComputeHdr => Some(wgpu::TextureFormat::Rgba16Float),
+        }
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        shared: Shared<'_>,
+        format: wgpu::TextureFormat,
+        target_format: wgpu::TextureFormat,
+        dimensions: wgpu::Extent3d,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("yuv converted texture"),
+            size: dimensions,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let storage_binding = match format {
+            wgpu::TextureFormat::Rgba16Float => 2,
+            _ => 1,
+        };
+
+        let storage_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("yuv converted storage bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: storage_binding,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                }],
+            });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("yuv converted texture bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                }],
+            });
+
+        let storage_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("yuv converted storage bind group"),
+            layout: &storage_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: storage_binding,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+        });
+
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("yuv converted texture bind group"),
+            layout: &texture_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+        });
+
+        let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yuv compute pipeline layout"),
+            bind_group_layouts: &[
+                shared.uniform_bind_group_layout,
+                shared.texture_bind_group_layout,
+                shared.lut_bind_group_layout,
+                &storage_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("yuv compute pipeline"),
+            layout: Some(&compute_layout),
+            module: shared.shader,
+            entry_point: match format {
+                wgpu::TextureFormat::Rgba16Float => "cs_rgba16f",
+                _ => "cs_rgba8",
+            },
+        });
+
+        let draw_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yuv converted pipeline layout"),
+            bind_group_layouts: &[
+                shared.uniform_bind_group_layout,
+                shared.texture_bind_group_layout,
+                shared.lut_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let draw_pipeline = super::render_pipeline(
+            device,
+            &draw_layout,
+            shared.shader,
+            target_format,
+            super::EntryPoints {
+                fragment: "fs_converted",
+                ..super::FRAME
+            },
+        );
+
+        let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yuv compute uniform buffer"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group = super::uniform_bind_group(
+            device,
+            "yuv compute uniform bind group",
+            shared.uniform_bind_group_layout,
+            &uniforms_buffer,
+            &super::mask::empty(device),
+        );
+
+        Self {
+            format,
+            dimensions,
+            compute_pipeline,
+            draw_pipeline,
+            uniforms_buffer,
+            uniform_bind_group,
+            storage_bind_group,
+            texture_bind_group,
+            converted: None,
+        }
+    }
+
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Whether the converted frame was encoded for SDR display, but drawn to a target
+    /// which expects linear values
+    pub fn decodes(&self, target_format: wgpu::TextureFormat) -> bool {
+        self.format == wgpu::TextureFormat::Rgba8Unorm
+            && (target_format.is_srgb() || target_format == wgpu::TextureFormat::Rgba16Float)
+    }
+
+    /// Forgets the latest conversion, e.g. because the LUT changed
+    pub fn invalidate(&mut self) {
+        self.converted = None;
+    }
+
+    /// Converts the frame bound by `textures` unless this `version` of it was already
+    /// converted with the same `uniforms`
+    pub fn convert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        version: u64,
+        uniforms: &Uniforms,
+        textures: &wgpu::BindGroup,
+        lut: &wgpu::BindGroup,
+    ) {
+        if let Some((converted, previous)) = &self.converted {
+            if *converted == version && bytemuck::bytes_of(previous) == bytemuck::bytes_of(uniforms)
+            {
+                return;
+            }
+        }
+
+        queue.write_buffer(&self.uniforms_buffer, 0, bytemuck::bytes_of(uniforms));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("yuv compute encoder"),
+        });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("yuv.compute.pass"),
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.compute_pipeline);
+            pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            pass.set_bind_group(1, textures, &[]);
+            pass.set_bind_group(2, lut, &[]);
+            pass.set_bind_group(3, &self.storage_bind_group, &[]);
+            pass.dispatch_workgroups(
+                self.dimensions.width.div_ceil(8),
+                self.dimensions.height.div_ceil(8),
+                1,
+            );
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        self.converted = Some((version, *uniforms));
+    }
+
+    /// Sets up `pass` to draw the converted frame, with the uniforms and textures of the
+    /// frame pipeline
+    pub fn bind<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        uniforms: &'a wgpu::BindGroup,
+        textures: &'a wgpu::BindGroup,
+        lut: &'a wgpu::BindGroup,
+    ) {
+        pass.set_pipeline(&self.draw_pipeline);
+        pass.set_bind_group(0, uniforms, &[]);
+        pass.set_bind_group(1, textures, &[]);
+        pass.set_bind_group(2, lut, &[]);
+        pass.set_bind_group(3, &self.texture_bind_group, &[]);
+    }
+}
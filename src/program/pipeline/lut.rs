@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use iced::widget::shader::wgpu;
+use iced::widget::shader::wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::lut::Table;
+
+/// Textures of the [`crate::Lut`] applied after conversion, bound as their own group
+pub struct Lut {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// LUT whose tables are bound, to upload each LUT once
+    bound: Option<Arc<crate::Lut>>,
+}
+
+/// Domains and sizes of the tables, where a size of 0 marks a missing table
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Uniforms {
+    cube_min: [f32; 4],
+    cube_max: [f32; 4],
+    shaper_min: [f32; 4],
+    shaper_max: [f32; 4],
+    cube_size: u32,
+    shaper_size: u32,
+    _padding: [u32; 2],
+}
+
+impl Lut {
+    pub fn new(device: &wgpu::Device, visibility: wgpu::ShaderStages) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("yuv lut bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Uniforms>() as u64
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = empty_bind_group(device, &bind_group_layout);
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            bound: None,
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Uploads the tables of `lut` unless they are bound already, returning whether they
+    /// were
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: Option<&Arc<crate::Lut>>,
+    ) -> bool {
+        let bound = match (&self.bound, lut) {
+            (Some(bound), Some(lut)) => Arc::ptr_eq(bound, lut),
+            (bound, lut) => bound.is_none() && lut.is_none(),
+        };
+
+        if bound {
+            return false;
+        }
+
+        self.bound = lut.cloned();
+
+        let Some(lut) = lut else {
+            self.bind_group = empty_bind_group(device, &self.bind_group_layout);
+            return true;
+        };
+
+        let texture = |table: Option<&Table>, dimension| match table {
+            Some(table) => table_texture(device, queue, table, dimension),
+            None => empty_texture(device, dimension),
+        };
+
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &texture(lut.cube.as_ref(), wgpu::TextureDimension::D3),
+            &texture(lut.shaper.as_ref(), wgpu::TextureDimension::D2),
+            lut.cube.as_ref(),
+            lut.shaper.as_ref(),
+        );
+
+        true
+    }
+}
+
+/// Without a LUT, single (unused) texels are bound instead
+fn empty_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+    create_bind_group(
+        device,
+        layout,
+        &empty_texture(device, wgpu::TextureDimension::D3),
+        &empty_texture(device, wgpu::TextureDimension::D2),
+        None,
+        None,
+    )
+}
+
+fn texture_descriptor(
+    size: wgpu::Extent3d,
+    dimension: wgpu::TextureDimension,
+) -> wgpu::TextureDescriptor<'static> {
+    wgpu::TextureDescriptor {
+        label: Some("yuv lut texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    }
+}
+
+fn empty_texture(device: &wgpu::Device, dimension: wgpu::TextureDimension) -> wgpu::TextureView {
+    let size = wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+
+    device
+        .create_texture(&texture_descriptor(size, dimension))
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// 3D tables are uploaded as cubes, 1D tables as rows which wrap at the largest width
+/// of 2D textures
+fn table_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    table: &Table,
+    dimension: wgpu::TextureDimension,
+) -> wgpu::TextureView {
+    let size = match dimension {
+        wgpu::TextureDimension::D3 => wgpu::Extent3d {
+            width: table.size,
+            height: table.size,
+            depth_or_array_layers: table.size,
+        },
+        _ => {
+            let width = table.size.min(device.limits().max_texture_dimension_2d);
+
+            wgpu::Extent3d {
+                width,
+                height: table.size.div_ceil(width),
+                depth_or_array_layers: 1,
+            }
+        }
+    };
+
+    // the last row of a wrapped table is padded
+    let mut texels: Vec<[f32; 4]> = table
+        .entries
+        .iter()
+        .map(|&[r, g, b]| [r, g, b, 1.0])
+        .collect();
+    texels.resize(
+        (size.width * size.height * size.depth_or_array_layers) as usize,
+        [0.0; 4],
+    );
+
+    device
+        .create_texture_with_data(
+            queue,
+            &texture_descriptor(size, dimension),
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&texels),
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    cube_view: &wgpu::TextureView,
+    shaper_view: &wgpu::TextureView,
+    cube: Option<&Table>,
+    shaper: Option<&Table>,
+) -> wgpu::BindGroup {
+    let domain = |table: Option<&Table>| {
+        let ([r0, g0, b0], [r1, g1, b1]) = table.map_or(([0.0; 3], [1.0; 3]), |table| {
+            (table.domain_min, table.domain_max)
+        });
+
+        ([r0, g0, b0, 0.0], [r1, g1, b1, 1.0])
+    };
+    let (cube_min, cube_max) = domain(cube);
+    let (shaper_min, shaper_max) = domain(shaper);
+
+    let uniforms = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("yuv lut uniform buffer"),
+        usage: wgpu::BufferUsages::UNIFORM,
+        contents: bytemuck::bytes_of(&Uniforms {
+            cube_min,
+            cube_max,
+            shaper_min,
+            shaper_max,
+            cube_size: cube.map_or(0, |table| table.size),
+            shaper_size: shaper.map_or(0, |table| table.size),
+            _padding: [0; 2],
+        }),
+    });
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("yuv lut bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(cube_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(shaper_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniforms.as_entire_binding(),
+            },
+        ],
+    })
+}
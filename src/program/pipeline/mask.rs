@@ -0,0 +1,43 @@
+use iced::widget::shader::wgpu;
+use iced::widget::shader::wgpu::util::DeviceExt;
+
+use crate::program::Mask;
+
+/// Texture bound where there is no [`Mask`], which is never sampled
+pub fn empty(device: &wgpu::Device) -> wgpu::TextureView {
+    device
+        .create_texture(&descriptor(1, 1))
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Uploads the coverage of the [`Mask`], which must be valid
+pub fn texture(device: &wgpu::Device, queue: &wgpu::Queue, mask: &Mask) -> wgpu::TextureView {
+    let Mask { alpha, dimensions } = mask;
+    let len = dimensions.width as usize * dimensions.height as usize;
+
+    device
+        .create_texture_with_data(
+            queue,
+            &descriptor(dimensions.width, dimensions.height),
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &alpha[..len],
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn descriptor(width: u32, height: u32) -> wgpu::TextureDescriptor<'static> {
+    wgpu::TextureDescriptor {
+        label: Some("yuv mask texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    }
+}
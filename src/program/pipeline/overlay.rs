@@ -0,0 +1,318 @@
+use iced::widget::shader::wgpu;
+use iced::widget::shader::wgpu::util::DeviceExt;
+use iced::Rectangle;
+
+use super::EntryPoints;
+use crate::program::Overlay;
+use crate::Size;
+
+/// Draws the RGBA bitmaps of [`Overlay`]s over the frame, in the render pass of the frame
+pub struct Overlays {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Overlays whose bitmaps were uploaded, in the order they are drawn
+    uploaded: Vec<Uploaded>,
+    instance_buffer: wgpu::Buffer,
+    instances: u32,
+}
+
+struct Uploaded {
+    overlay: Overlay,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Overlays {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        shader: &wgpu::ShaderModule,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // the bindings follow the planes of the frame, sharing their sampler
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("yuv overlay bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yuv overlay pipeline layout"),
+            bind_group_layouts: &[uniform_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::render_pipeline(
+            device,
+            &layout,
+            shader,
+            format,
+            EntryPoints {
+                vertex: "vs_overlay",
+                fragment: "fs_overlay",
+                instance: Instance::desc,
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler: super::sampler(device),
+            uploaded: Vec::new(),
+            instance_buffer: instance_buffer(device, 4),
+            instances: 0,
+        }
+    }
+
+    /// Uploads the bitmaps of `overlays` which changed and positions them over the frame
+    /// drawn within `bounds`
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        overlays: &[Overlay],
+        bounds: Rectangle,
+        image_dimensions: Size,
+        target_size: Size,
+    ) {
+        let mut uploaded = std::mem::take(&mut self.uploaded);
+
+        self.uploaded = overlays
+            .iter()
+            .map(|overlay| {
+                let bound = uploaded.iter().position(|uploaded| {
+                    uploaded.overlay.rgba.ptr_eq(&overlay.rgba)
+                        && uploaded.overlay.dimensions == overlay.dimensions
+                });
+
+                match bound {
+                    Some(index) => Uploaded {
+                        overlay: overlay.clone(),
+                        ..uploaded.swap_remove(index)
+                    },
+                    None => Uploaded {
+                        overlay: overlay.clone(),
+                        bind_group: self.upload(device, queue, overlay),
+                    },
+                }
+            })
+            .collect();
+
+        let instances: Vec<_> = overlays
+            .iter()
+            .map(|overlay| {
+                Instance::new(
+                    within(overlay.bounds, bounds, image_dimensions),
+                    target_size,
+                )
+            })
+            .collect();
+
+        let size = std::mem::size_of_val(instances.as_slice()) as u64;
+
+        if size > self.instance_buffer.size() {
+            self.instance_buffer = instance_buffer(device, instances.len().next_power_of_two());
+        }
+
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.instances = instances.len() as u32;
+    }
+
+    fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        overlay: &Overlay,
+    ) -> wgpu::BindGroup {
+        let Size { width, height } = overlay.dimensions;
+        let len = width as usize * height as usize * 4;
+
+        let view = device
+            .create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("yuv overlay texture"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                &overlay.rgba[..len],
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("yuv overlay bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+        })
+    }
+
+    /// Draws the overlays over the frame drawn by `pass`, with the uniforms of the frame
+    pub fn render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, uniforms: &'a wgpu::BindGroup) {
+        if self.instances == 0 {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, uniforms, &[]);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+
+        for (instance, uploaded) in (0..self.instances).zip(&self.uploaded) {
+            pass.set_bind_group(1, &uploaded.bind_group, &[]);
+            pass.draw(0..6, instance..instance + 1);
+        }
+    }
+}
+
+/// Bounds of an overlay
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Instance {
+    /// Left, bottom, width and height in clip space
+    bounds: [f32; 4],
+}
+
+impl Instance {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    /// Overlay drawn within `bounds`, given in logical pixels
+    fn new(bounds: Rectangle, target_size: Size) -> Self {
+        Self {
+            bounds: [
+                -1.0 + (bounds.x / target_size.width) * 2.0,
+                -1.0 + ((target_size.height - (bounds.height + bounds.y)) / target_size.height)
+                    * 2.0,
+                bounds.width / target_size.width * 2.0,
+                bounds.height / target_size.height * 2.0,
+            ],
+        }
+    }
+}
+
+/// Maps `frame_bounds`, given in pixels of the frame, to logical pixels of the target where
+/// the frame is drawn within `bounds`
+fn within(frame_bounds: Rectangle, bounds: Rectangle, image_dimensions: Size) -> Rectangle {
+    let scale_x = bounds.width / image_dimensions.width;
+    let scale_y = bounds.height / image_dimensions.height;
+
+    Rectangle {
+        x: bounds.x + frame_bounds.x * scale_x,
+        y: bounds.y + frame_bounds.y * scale_y,
+        width: frame_bounds.width * scale_x,
+        height: frame_bounds.height * scale_y,
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, overlays: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("yuv overlay instance buffer"),
+        size: (overlays * std::mem::size_of::<Instance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_are_mapped_to_clip_space() {
+        let target_size = Size {
+            width: 200.0,
+            height: 100.0,
+        };
+        let instance = |x, y, width, height| {
+            Instance::new(
+                Rectangle {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+                target_size,
+            )
+            .bounds
+        };
+
+        assert_eq!(instance(0.0, 0.0, 200.0, 100.0), [-1.0, -1.0, 2.0, 2.0]);
+        // the top left quarter, whose bottom edge is at the center
+        assert_eq!(instance(0.0, 0.0, 100.0, 50.0), [-1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(instance(150.0, 75.0, 50.0, 25.0), [0.5, -1.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn frame_pixels_are_scaled_to_the_drawn_frame() {
+        // a 1920x1080 frame drawn at half its size, offset by 10 logical pixels
+        let bounds = Rectangle {
+            x: 10.0,
+            y: 20.0,
+            width: 960.0,
+            height: 540.0,
+        };
+        let image_dimensions = Size {
+            width: 1920.0,
+            height: 1080.0,
+        };
+        let subtitle = Rectangle {
+            x: 480.0,
+            y: 900.0,
+            width: 960.0,
+            height: 120.0,
+        };
+
+        assert_eq!(
+            within(subtitle, bounds, image_dimensions),
+            Rectangle {
+                x: 250.0,
+                y: 470.0,
+                width: 480.0,
+                height: 60.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use iced::widget::shader::wgpu;
+use iced::Rectangle;
+
+use super::instance::Instance;
+use super::Uniforms;
+use crate::program::screenshot::{Request, Snapshot};
+use crate::Size;
+
+/// Format frames are read back in
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Renders the current frame offscreen, with uniforms and vertices of its own so that
+/// the frame on screen isn't affected
+pub struct Readback {
+    pipeline: wgpu::RenderPipeline,
+    uniforms_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl Readback {
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline: wgpu::RenderPipeline,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        mask: &wgpu::TextureView,
+    ) -> Self {
+        let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yuv readback uniform buffer"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group = super::uniform_bind_group(
+            device,
+            "yuv readback uniform bind group",
+            uniform_bind_group_layout,
+            &uniforms_buffer,
+            mask,
+        );
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yuv readback vertex buffer"),
+            size: std::mem::size_of::<Instance>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            uniforms_buffer,
+            uniform_bind_group,
+            vertex_buffer,
+        }
+    }
+
+    /// Binds the texture of the alpha mask drawn on screen as well
+    pub fn update_mask(
+        &mut self,
+        device: &wgpu::Device,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        mask: &wgpu::TextureView,
+    ) {
+        self.uniform_bind_group = super::uniform_bind_group(
+            device,
+            "yuv readback uniform bind group",
+            uniform_bind_group_layout,
+            &self.uniforms_buffer,
+            mask,
+        );
+    }
+
+    /// Renders the frame bound by `textures` into a new texture of `dimensions`, which
+    /// must not be empty
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: Size<u32>,
+        uniforms: &Uniforms,
+        textures: &wgpu::BindGroup,
+        lut: &wgpu::BindGroup,
+    ) -> wgpu::Texture {
+        let extent = wgpu::Extent3d {
+            width: dimensions.width,
+            height: dimensions.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("yuv readback texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let size = Size::from(dimensions);
+        let bounds = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: size.width,
+            height: size.height,
+        };
+
+        queue.write_buffer(&self.uniforms_buffer, 0, bytemuck::bytes_of(uniforms));
+        queue.write_buffer(
+            &self.vertex_buffer,
+            0,
+            bytemuck::bytes_of(&Instance::frame(bounds, size)),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("yuv readback encoder"),
+        });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("yuv.readback.pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            pass.set_bind_group(1, textures, &[]);
+            pass.set_bind_group(2, lut, &[]);
+            pass.draw(0..6, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        texture
+    }
+}
+
+/// Copies a texture of [`FORMAT`] to a buffer and completes the `request` once the buffer
+/// is mapped
+pub fn read(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, request: Request) {
+    let dimensions = Size {
+        width: texture.width(),
+        height: texture.height(),
+    };
+
+    // rows of buffers copied from textures are aligned
+    let row = dimensions.width as usize * 4;
+    let bytes_per_row = row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+
+    let buffer = Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("yuv readback buffer"),
+        size: (bytes_per_row * dimensions.height as usize) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    }));
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("yuv readback copy encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row as u32),
+                rows_per_image: Some(dimensions.height),
+            },
+        },
+        texture.size(),
+    );
+
+    queue.submit(Some(encoder.finish()));
+
+    let mapped = buffer.clone();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_err() {
+                return;
+            }
+
+            let rgba = mapped
+                .slice(..)
+                .get_mapped_range()
+                .chunks(bytes_per_row)
+                .flat_map(|padded| &padded[..row])
+                .copied()
+                .collect();
+            mapped.unmap();
+
+            request.complete(Snapshot { dimensions, rgba });
+        });
+}
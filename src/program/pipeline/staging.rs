@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use iced::widget::shader::wgpu;
+
+use super::Plane;
+
+/// Buffer the planes of a frame are written to while it is mapped, then copied to a
+/// texture on the GPU
+///
+/// The buffer is mapped again once the GPU is done copying, so writing the next frame
+/// never waits on the GPU.
+pub struct Staging {
+    buffer: Arc<wgpu::Buffer>,
+    mapped: Arc<AtomicBool>,
+}
+
+impl Staging {
+    pub fn new(device: &wgpu::Device, planes: &[Plane<'_>; 3]) -> Self {
+        Self {
+            buffer: Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("yuv staging buffer"),
+                size: size(planes),
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            })),
+            mapped: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Whether the buffer can hold the planes, whose rows may be longer than those of the
+    /// frame it was created for
+    pub fn fits(&self, planes: &[Plane<'_>; 3]) -> bool {
+        size(planes) <= self.buffer.size()
+    }
+
+    /// Whether the buffer can be written, i.e. the GPU is done with the previous upload
+    pub fn is_mapped(&self) -> bool {
+        self.mapped.load(Ordering::Acquire)
+    }
+
+    /// Writes the planes to the buffer and encodes copying them to the layers of `texture`,
+    /// the buffer must be mapped
+    pub fn upload(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        planes: &[Plane<'_>; 3],
+    ) {
+        let mut offset = 0;
+
+        {
+            let mut range = self.buffer.slice(..).get_mapped_range_mut();
+
+            for (layer, plane) in planes.iter().enumerate() {
+                // rows of buffers copied to textures are aligned
+                let bytes_per_row = padded(plane.bytes_per_row);
+                let rows = plane.extent.height as usize;
+                let row = plane.bytes_per_row as usize;
+
+                let staged = &mut range[offset..offset + bytes_per_row as usize * rows];
+
+                for (target, source) in staged
+                    .chunks_mut(bytes_per_row as usize)
+                    .zip(plane.data.chunks(row))
+                {
+                    target[..source.len()].copy_from_slice(source);
+                }
+
+                encoder.copy_buffer_to_texture(
+                    wgpu::ImageCopyBuffer {
+                        buffer: &self.buffer,
+                        layout: wgpu::ImageDataLayout {
+                            offset: offset as u64,
+                            bytes_per_row: Some(bytes_per_row),
+                            rows_per_image: Some(plane.extent.height),
+                        },
+                    },
+                    wgpu::ImageCopyTexture {
+                        texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::default(),
+                    },
+                    plane.extent,
+                );
+
+                offset += bytes_per_row as usize * rows;
+            }
+        }
+
+        self.buffer.unmap();
+        self.mapped.store(false, Ordering::Release);
+    }
+
+    /// Maps the buffer again once the copies encoded by [`Staging::upload`] are done,
+    /// which must be called after submitting them
+    pub fn recycle(&self) {
+        let mapped = self.mapped.clone();
+
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Write, move |result| {
+                mapped.store(result.is_ok(), Ordering::Release);
+            });
+    }
+}
+
+/// Bytes of the planes with padded rows
+fn size(planes: &[Plane<'_>; 3]) -> u64 {
+    planes
+        .iter()
+        .map(|plane| padded(plane.bytes_per_row) as u64 * plane.extent.height as u64)
+        .sum()
+}
+
+fn padded(bytes_per_row: u32) -> u32 {
+    bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
@@ -1,6 +1,10 @@
-use glam::Vec2;
+use glam::{Vec2, Vec3};
+use iced::widget::shader::wgpu;
+use iced::{Border, Color, Rectangle};
 
-use crate::Size;
+use crate::program::privacy::Regions;
+use crate::program::{ChromaFilter, Deinterlace, GamutMapping, Settings, ToneMapping};
+use crate::{ColorPrimaries, FieldOrder, MatrixCoefficients, Size, TransferFunction};
 
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
@@ -8,6 +12,58 @@ pub struct Uniforms {
     sampling_factor: Vec2,
     size: Vec2,
     scale: Vec2,
+    chroma_offset: Vec2,
+    chroma_filter: u32,
+    deinterlace: u32,
+    first_field: u32,
+    transfer: u32,
+    output_transfer: u32,
+    tone_mapping: u32,
+    /// Peak luminance of the frame relative to SDR reference white
+    peak: f32,
+    _padding: u32,
+    /// Columns of the conversion from the primaries of the frame to BT.709
+    gamut: [[f32; 4]; 3],
+    gamut_mapping: u32,
+    output: u32,
+    brightness: f32,
+    contrast: f32,
+    /// Columns of the rotation and scale applied to chroma
+    chroma_adjustment: [f32; 4],
+    gamma: f32,
+    lut_strength: f32,
+    _padding_2: [u32; 2],
+    /// Offset subtracted from the samples of each plane
+    yuv_offset: [f32; 4],
+    /// Scale of each plane after the offset, normalizing the range
+    yuv_scale: [f32; 4],
+    /// Columns of the conversion from normalized YUV to non-linear RGB (or LMS)
+    yuv_matrix: [[f32; 4]; 3],
+    ictcp: u32,
+    /// Whether frames converted to encoded RGB are decoded to linear when drawn
+    decode: u32,
+    border_width: f32,
+    mask: u32,
+    /// Bounds of the widget in physical pixels, empty when drawn off screen
+    origin: [f32; 2],
+    extent: [f32; 2],
+    /// Corner radii from the top left corner clockwise
+    radius: [f32; 4],
+    border_color: [f32; 4],
+    opacity: f32,
+    /// Progress of the cross-fade from the previous frame, 1.0 once it is done
+    fade: f32,
+    _padding_4: [u32; 2],
+    /// Values of the post-processing hook
+    post: [[f32; 4]; 4],
+    /// Normalized chroma of the key color
+    key: [f32; 2],
+    key_similarity: f32,
+    key_smoothness: f32,
+    key_spill: f32,
+    chroma_key: u32,
+    _padding_5: [u32; 2],
+    privacy: Regions,
 }
 
 impl Uniforms {
@@ -16,6 +72,8 @@ impl Uniforms {
         image_dimensions: Size,
         sampling_factor: f32,
         target_size: Size,
+        format: wgpu::TextureFormat,
+        settings: &Settings,
     ) -> Self {
         let image_dimensions: Vec2 = (image_dimensions.width, image_dimensions.height).into();
         let scale = (
@@ -24,10 +82,157 @@ impl Uniforms {
         )
             .into();
 
-        Self {
+        // Shift from a luma sample to the chroma sample covering it, in texture coordinates
+        let chroma_offset = if sampling_factor > 1.0 {
+            let (x, y) = settings.chroma_location.position();
+
+            Vec2::new(
+                0.5 * (sampling_factor - 1.0) - x,
+                0.5 * (sampling_factor - 1.0) - y,
+            ) / image_dimensions
+        } else {
+            Vec2::ZERO
+        };
+
+        // the key color in the YUV of the frame, to compare its chroma
+        let chroma_key = settings.chroma_key.unwrap_or_default();
+        let Color { r, g, b, .. } = chroma_key.color;
+        let key = settings.matrix.to_rgb(settings.transfer).inverse() * Vec3::new(r, g, b);
+
+        let mut uniforms = Self {
             sampling_factor: (sampling_factor, sampling_factor).into(),
             size: (size.width, size.height).into(),
             scale,
+            chroma_offset,
+            chroma_filter: match settings.chroma_filter {
+                ChromaFilter::Bilinear => 0,
+                ChromaFilter::Bicubic => 1,
+            },
+            deinterlace: match (settings.field_order, settings.deinterlace) {
+                (FieldOrder::Progressive, _) | (_, Deinterlace::Weave) => 0,
+                (_, Deinterlace::Bob) => 1,
+                (_, Deinterlace::Blend) => 2,
+                (_, Deinterlace::Adaptive) => 3,
+            },
+            first_field: match settings.field_order {
+                FieldOrder::BottomFieldFirst => 1,
+                FieldOrder::Progressive | FieldOrder::TopFieldFirst => 0,
+            },
+            transfer: transfer(settings.transfer),
+            output_transfer: transfer(settings.output_transfer()),
+            tone_mapping: match settings.tone_mapping {
+                ToneMapping::Bt2390 => 0,
+                ToneMapping::Hable => 1,
+                ToneMapping::Reinhard => 2,
+                ToneMapping::Clip => 3,
+            },
+            peak: settings.peak(),
+            _padding: 0,
+            gamut: settings
+                .primaries
+                .conversion(ColorPrimaries::Bt709)
+                .to_cols_array_2d()
+                .map(|[x, y, z]| [x, y, z, 0.0]),
+            gamut_mapping: match (settings.primaries, settings.gamut_mapping) {
+                (ColorPrimaries::Bt709, _) => 0,
+                (_, GamutMapping::Clip) => 1,
+                (_, GamutMapping::Compress) => 2,
+            },
+            output: if format == wgpu::TextureFormat::Rgba16Float {
+                2
+            } else if format.is_srgb() {
+                1
+            } else {
+                0
+            },
+            brightness: settings.adjustments.brightness,
+            contrast: settings.adjustments.contrast,
+            chroma_adjustment: settings.adjustments.chroma().to_cols_array(),
+            gamma: settings.adjustments.clamped_gamma(),
+            lut_strength: settings.lut_strength,
+            _padding_2: [0; 2],
+            yuv_offset: settings.range.offset().extend(0.0).into(),
+            yuv_scale: settings.range.scale().extend(0.0).into(),
+            yuv_matrix: settings
+                .matrix
+                .to_rgb(settings.transfer)
+                .to_cols_array_2d()
+                .map(|[x, y, z]| [x, y, z, 0.0]),
+            ictcp: (settings.matrix == MatrixCoefficients::ICtCp).into(),
+            decode: 0,
+            border_width: 0.0,
+            mask: 0,
+            origin: [0.0; 2],
+            extent: [0.0; 2],
+            radius: [0.0; 4],
+            border_color: [0.0; 4],
+            opacity: 1.0,
+            fade: 1.0,
+            _padding_4: [0; 2],
+            post: settings.post_uniforms,
+            key: [key.y, key.z],
+            key_similarity: chroma_key.similarity,
+            key_smoothness: chroma_key.smoothness,
+            key_spill: chroma_key.spill,
+            chroma_key: settings.chroma_key.is_some().into(),
+            _padding_5: [0; 2],
+            privacy: settings.privacy,
+        };
+
+        // like the border, solid regions are given in sRGB
+        if uniforms.output != 0 {
+            uniforms.privacy = uniforms.privacy.linear();
         }
+
+        uniforms
+    }
+
+    /// Decodes the sRGB of frames converted to encoded RGB when they are drawn
+    pub fn decode(mut self, decode: bool) -> Self {
+        self.decode = decode.into();
+        self
+    }
+
+    /// Draws the frame with `opacity`, blending in the previous frame until `fade` reaches 1.0
+    pub fn transition(mut self, opacity: f32, fade: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self.fade = fade;
+        self
+    }
+
+    /// Clips the frame to the widget at `bounds` with the corners and border of `border`,
+    /// and applies the alpha mask if there is one
+    pub fn shape(
+        mut self,
+        bounds: Rectangle,
+        scale_factor: f32,
+        border: Border,
+        mask: bool,
+    ) -> Self {
+        let radius: [f32; 4] = border.radius.into();
+        let Color { r, g, b, a } = border.color;
+
+        self.border_width = border.width * scale_factor;
+        self.mask = mask.into();
+        self.origin = [bounds.x * scale_factor, bounds.y * scale_factor];
+        self.extent = [bounds.width * scale_factor, bounds.height * scale_factor];
+        self.radius = radius.map(|radius| radius * scale_factor);
+        // colors are given in sRGB, like the frames written to encoded targets
+        self.border_color = if self.output == 0 {
+            [r, g, b, a]
+        } else {
+            border.color.into_linear()
+        };
+        self
+    }
+}
+
+fn transfer(transfer: TransferFunction) -> u32 {
+    match transfer {
+        TransferFunction::Bt709 => 0,
+        TransferFunction::Srgb => 1,
+        TransferFunction::Linear => 2,
+        TransferFunction::Pq => 3,
+        TransferFunction::Hlg => 4,
     }
 }
@@ -0,0 +1,348 @@
+use iced::widget::shader::wgpu;
+use iced::Rectangle;
+
+use super::lut::Lut;
+use super::{planes, EntryPoints, Uniforms};
+use crate::program::Settings;
+use crate::{PostProcess, Renderable, Size};
+
+/// Draws the tiles of a video wall with one instanced draw, their planes are uploaded to
+/// the layers of a single texture array
+pub struct Wall {
+    pipeline: wgpu::RenderPipeline,
+    uniforms_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: Option<Textures>,
+    lut: Lut,
+    instance_buffer: wgpu::Buffer,
+    instances: u32,
+    scale_factor: f32,
+}
+
+/// Texture array holding three layers per tile
+struct Textures {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    dimensions: Size<u32>,
+    /// Version of the frame uploaded for each tile, 0 for none
+    versions: Vec<u64>,
+}
+
+impl Wall {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let stages = wgpu::ShaderStages::FRAGMENT;
+
+        let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("yuv wall uniform buffer"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group_layout = super::uniform_bind_group_layout(device, stages);
+
+        let uniform_bind_group = super::uniform_bind_group(
+            device,
+            "yuv wall uniform bind group",
+            &uniform_bind_group_layout,
+            &uniforms_buffer,
+            &super::mask::empty(device),
+        );
+
+        let texture_bind_group_layout = super::texture_bind_group_layout(device, stages);
+        let lut = Lut::new(device, stages);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("yuv wall pipeline layout"),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &texture_bind_group_layout,
+                lut.bind_group_layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::render_pipeline(
+            device,
+            &layout,
+            &super::shader(device, &PostProcess::default()),
+            format,
+            EntryPoints {
+                vertex: "vs_tile",
+                fragment: "fs_tile",
+                instance: Tile::desc,
+            },
+        );
+
+        Self {
+            pipeline,
+            uniforms_buffer,
+            uniform_bind_group,
+            texture_bind_group_layout,
+            sampler: super::sampler(device),
+            textures: None,
+            lut,
+            instance_buffer: instance_buffer(device, 16),
+            instances: 0,
+            scale_factor: 1.0,
+        }
+    }
+
+    /// Uploads the frames of the tiles which changed, with their versions
+    ///
+    /// The texture array is created again, uploading every frame, when tiles are added
+    /// or the dimensions of the frames change.
+    pub fn update_frames<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frames: impl ExactSizeIterator<Item = (u64, &'a Renderable)> + Clone,
+    ) {
+        let Some((_, first)) = frames.clone().next() else {
+            return;
+        };
+        let dimensions = first.dimensions();
+
+        let textures = match self.textures.take() {
+            Some(textures)
+                if textures.dimensions == dimensions && textures.versions.len() >= frames.len() =>
+            {
+                textures
+            }
+            _ => Textures::new(
+                device,
+                &self.texture_bind_group_layout,
+                &self.sampler,
+                dimensions,
+                frames.len(),
+            ),
+        };
+        let textures = self.textures.insert(textures);
+
+        for (tile, (version, yuv)) in frames.enumerate() {
+            // tiles are converted with the sampling factor of the first one
+            if textures.versions[tile] == version
+                || yuv.y().is_empty()
+                || yuv.dimensions() != dimensions
+                || yuv.downsampling_factor() != first.downsampling_factor()
+            {
+                continue;
+            }
+
+            for (layer, plane) in planes(yuv).iter().enumerate() {
+                plane.write(queue, &textures.texture, (tile * 3 + layer) as u32, None);
+            }
+
+            textures.versions[tile] = version;
+        }
+    }
+
+    /// Whether the frame of `version` was uploaded for `tile`
+    pub fn has_frame(&self, tile: usize, version: u64) -> bool {
+        self.textures
+            .as_ref()
+            .and_then(|textures| textures.versions.get(tile))
+            .is_some_and(|uploaded| *uploaded == version)
+    }
+
+    pub fn update_uniforms(&mut self, queue: &wgpu::Queue, uniforms: &Uniforms) {
+        queue.write_buffer(&self.uniforms_buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+
+    /// Sets the tiles to draw
+    pub fn update_tiles(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[Tile],
+        scale_factor: f32,
+    ) {
+        let size = std::mem::size_of_val(tiles) as u64;
+
+        if size > self.instance_buffer.size() {
+            self.instance_buffer = instance_buffer(device, tiles.len().next_power_of_two());
+        }
+
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(tiles));
+        self.instances = tiles.len() as u32;
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn render(
+        &self,
+        target: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+        bounds: Rectangle,
+    ) {
+        let Some(textures) = &self.textures else {
+            return;
+        };
+
+        if self.instances == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("yuv.wall.pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_scissor_rect(
+            (bounds.x * self.scale_factor) as u32,
+            (bounds.y * self.scale_factor) as u32,
+            (bounds.width * self.scale_factor) as u32,
+            (bounds.height * self.scale_factor) as u32,
+        );
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        pass.set_bind_group(1, &textures.bind_group, &[]);
+        pass.set_bind_group(2, self.lut.bind_group(), &[]);
+
+        pass.draw(0..6, 0..self.instances);
+    }
+}
+
+impl Textures {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        dimensions: Size<u32>,
+        tiles: usize,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("yuv wall texture"),
+            size: wgpu::Extent3d {
+                width: dimensions.width,
+                height: dimensions.height,
+                depth_or_array_layers: tiles as u32 * 3,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        // tiles are never deinterlaced, so no previous frame is bound
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("yuv wall texture bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+            ],
+        });
+
+        Self {
+            texture,
+            bind_group,
+            dimensions,
+            versions: vec![0; tiles],
+        }
+    }
+}
+
+/// Instance of a tile, with its bounds, the first layer of its planes and the conversion
+/// of its samples to non-linear RGB
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Tile {
+    /// Left, bottom, width and height in clip space
+    bounds: [f32; 4],
+    layer: u32,
+    offset: [f32; 3],
+    scale: [f32; 3],
+    /// Columns of the conversion from normalized YUV to non-linear RGB
+    matrix: [[f32; 3]; 3],
+    /// Columns of the rotation and scale applied to chroma
+    chroma: [f32; 4],
+    /// Brightness, contrast and gamma
+    luma: [f32; 3],
+}
+
+impl Tile {
+    const ATTRIBS: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Uint32,
+        2 => Float32x3,
+        3 => Float32x3,
+        4 => Float32x3,
+        5 => Float32x3,
+        6 => Float32x3,
+        7 => Float32x4,
+        8 => Float32x3,
+    ];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    /// Tile drawing the frame uploaded for `index` within `bounds`, given in logical pixels
+    pub fn new(index: usize, bounds: Rectangle, target_size: Size, settings: &Settings) -> Self {
+        let adjustments = &settings.adjustments;
+
+        Self {
+            bounds: [
+                -1.0 + (bounds.x / target_size.width) * 2.0,
+                -1.0 + ((target_size.height - (bounds.height + bounds.y)) / target_size.height)
+                    * 2.0,
+                bounds.width / target_size.width * 2.0,
+                bounds.height / target_size.height * 2.0,
+            ],
+            layer: index as u32 * 3,
+            offset: settings.range.offset().into(),
+            scale: settings.range.scale().into(),
+            matrix: settings.matrix.to_rgb(settings.transfer).to_cols_array_2d(),
+            chroma: adjustments.chroma().to_cols_array(),
+            luma: [
+                adjustments.brightness,
+                adjustments.contrast,
+                adjustments.gamma,
+            ],
+        }
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, tiles: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("yuv wall instance buffer"),
+        size: (tiles * std::mem::size_of::<Tile>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,294 @@
+use iced::{Color, Point, Rectangle};
+
+/// Most regions drawn at once, further regions are hidden together by their bounding box
+pub const MAX_PRIVACY_REGIONS: usize = 16;
+
+/// Most vertices of all polygons drawn at once, further polygons are hidden by their
+/// bounding box
+pub const MAX_PRIVACY_VERTICES: usize = 128;
+
+/// Region of a frame hidden when it is drawn, e.g. a face or a license plate, leaving the
+/// frame itself untouched
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivacyRegion {
+    pub shape: RegionShape,
+    pub redaction: Redaction,
+}
+
+impl PrivacyRegion {
+    /// Hides `bounds`, given relative to the frame from 0.0 to 1.0.
+    pub fn rectangle(bounds: Rectangle, redaction: Redaction) -> Self {
+        Self {
+            shape: RegionShape::Rectangle(bounds),
+            redaction,
+        }
+    }
+
+    /// Hides the polygon with `points`, given relative to the frame from 0.0 to 1.0.
+    pub fn polygon(points: impl IntoIterator<Item = Point>, redaction: Redaction) -> Self {
+        Self {
+            shape: RegionShape::Polygon(points.into_iter().collect()),
+            redaction,
+        }
+    }
+}
+
+/// Outline of a [`PrivacyRegion`], relative to the frame from 0.0 to 1.0
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionShape {
+    Rectangle(Rectangle),
+    Polygon(Vec<Point>),
+}
+
+impl RegionShape {
+    /// Left, top, right and bottom edges
+    fn bounds(&self) -> [f32; 4] {
+        match self {
+            Self::Rectangle(bounds) => [
+                bounds.x,
+                bounds.y,
+                bounds.x + bounds.width,
+                bounds.y + bounds.height,
+            ],
+            Self::Polygon(points) => points.iter().fold(
+                [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+                |[left, top, right, bottom], point| {
+                    [
+                        left.min(point.x),
+                        top.min(point.y),
+                        right.max(point.x),
+                        bottom.max(point.y),
+                    ]
+                },
+            ),
+        }
+    }
+}
+
+/// How a [`PrivacyRegion`] is hidden
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redaction {
+    /// Filled with a color
+    Solid(Color),
+    /// Blocks of the given size in pixels of the frame, each showing the color at its center
+    Pixelate(f32),
+    /// Gaussian blur of the given radius in pixels of the frame
+    Blur(f32),
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self::Solid(Color::BLACK)
+    }
+}
+
+/// Privacy regions laid out for the shader
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub(crate) struct Regions {
+    len: u32,
+    _padding: [u32; 3],
+    regions: [Region; MAX_PRIVACY_REGIONS],
+    /// Two vertices in each element
+    vertices: [[f32; 4]; MAX_PRIVACY_VERTICES / 2],
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Region {
+    /// Left, top, right and bottom edges
+    bounds: [f32; 4],
+    color: [f32; 4],
+    /// Solid, pixelate or blur
+    mode: u32,
+    /// Size of the blocks or radius of the blur
+    strength: f32,
+    first_vertex: u32,
+    /// Vertices of the polygon, 0 for the whole bounds
+    vertices: u32,
+}
+
+impl Regions {
+    pub fn new(privacy_regions: &[PrivacyRegion]) -> Self {
+        let mut regions = Self::default();
+        let mut vertices = 0;
+
+        for privacy_region in privacy_regions {
+            let (mode, strength, color) = match privacy_region.redaction {
+                Redaction::Solid(color) => (0, 0.0, color),
+                Redaction::Pixelate(size) => (1, size.max(1.0), Color::BLACK),
+                Redaction::Blur(radius) => (2, radius.max(0.0), Color::BLACK),
+            };
+
+            let mut region = Region {
+                bounds: privacy_region.shape.bounds(),
+                color: [color.r, color.g, color.b, color.a],
+                mode,
+                strength,
+                ..Region::default()
+            };
+
+            // regions which don't fit are hidden with the last one, rather than not at all
+            if regions.len as usize == MAX_PRIVACY_REGIONS {
+                let last = &mut regions.regions[MAX_PRIVACY_REGIONS - 1];
+                let [left, top, right, bottom] = last.bounds;
+
+                region.bounds = [
+                    left.min(region.bounds[0]),
+                    top.min(region.bounds[1]),
+                    right.max(region.bounds[2]),
+                    bottom.max(region.bounds[3]),
+                ];
+                *last = region;
+
+                continue;
+            }
+
+            if let RegionShape::Polygon(points) = &privacy_region.shape {
+                if points.len() >= 3 && vertices + points.len() <= MAX_PRIVACY_VERTICES {
+                    for (i, point) in points.iter().enumerate() {
+                        let vertex = vertices + i;
+                        regions.vertices[vertex / 2][vertex % 2 * 2] = point.x;
+                        regions.vertices[vertex / 2][vertex % 2 * 2 + 1] = point.y;
+                    }
+
+                    region.first_vertex = vertices as u32;
+                    region.vertices = points.len() as u32;
+                    vertices += points.len();
+                }
+            }
+
+            regions.regions[regions.len as usize] = region;
+            regions.len += 1;
+        }
+
+        regions
+    }
+
+    /// Redaction of the last region containing `uv`, given relative to the frame, which is
+    /// the region the shader draws where regions overlap
+    pub fn redaction(&self, uv: Point) -> Option<Redaction> {
+        self.regions[..self.len as usize]
+            .iter()
+            .rev()
+            .find(|region| region.contains(uv, self))
+            .map(Region::redaction)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether any region is blurred
+    pub fn blurs(&self) -> bool {
+        self.regions[..self.len as usize]
+            .iter()
+            .any(|region| region.mode == 2)
+    }
+
+    fn vertex(&self, index: u32) -> Point {
+        let [x0, y0, x1, y1] = self.vertices[index as usize / 2];
+
+        if index & 1 == 1 {
+            Point::new(x1, y1)
+        } else {
+            Point::new(x0, y0)
+        }
+    }
+
+    /// Decodes the colors of solid regions for targets which expect linear values
+    pub fn linear(mut self) -> Self {
+        for region in &mut self.regions {
+            let [r, g, b, a] = region.color;
+            region.color = Color { r, g, b, a }.into_linear();
+        }
+
+        self
+    }
+}
+
+impl Region {
+    fn contains(&self, uv: Point, regions: &Regions) -> bool {
+        let [left, top, right, bottom] = self.bounds;
+
+        if uv.x < left || uv.y < top || uv.x > right || uv.y > bottom {
+            return false;
+        }
+
+        if self.vertices == 0 {
+            return true;
+        }
+
+        // even-odd rule, like the shader
+        let mut inside = false;
+        let mut previous = regions.vertex(self.first_vertex + self.vertices - 1);
+
+        for i in 0..self.vertices {
+            let current = regions.vertex(self.first_vertex + i);
+
+            if (current.y > uv.y) != (previous.y > uv.y) {
+                let x = current.x
+                    + (uv.y - current.y) / (previous.y - current.y) * (previous.x - current.x);
+
+                if uv.x < x {
+                    inside = !inside;
+                }
+            }
+
+            previous = current;
+        }
+
+        inside
+    }
+
+    fn redaction(&self) -> Redaction {
+        let [r, g, b, a] = self.color;
+
+        match self.mode {
+            1 => Redaction::Pixelate(self.strength),
+            2 => Redaction::Blur(self.strength),
+            _ => Redaction::Solid(Color { r, g, b, a }),
+        }
+    }
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_beyond_the_limit_are_hidden_by_their_bounds() {
+        let region = |x: f32| {
+            PrivacyRegion::polygon(
+                [
+                    Point::new(x, 0.1),
+                    Point::new(x + 0.01, 0.1),
+                    Point::new(x, 0.2),
+                ],
+                Redaction::default(),
+            )
+        };
+
+        let privacy_regions: Vec<_> = (0..MAX_PRIVACY_REGIONS + 2)
+            .map(|i| region(i as f32 * 0.01))
+            .collect();
+        let regions = Regions::new(&privacy_regions);
+
+        assert_eq!(regions.len as usize, MAX_PRIVACY_REGIONS);
+        assert_eq!(regions.regions[0].vertices, 3);
+
+        let last = regions.regions[MAX_PRIVACY_REGIONS - 1];
+        assert_eq!(last.vertices, 0);
+        assert_eq!(last.bounds[0], (MAX_PRIVACY_REGIONS - 1) as f32 * 0.01);
+        assert_eq!(
+            last.bounds[2],
+            (MAX_PRIVACY_REGIONS + 1) as f32 * 0.01 + 0.01
+        );
+    }
+}
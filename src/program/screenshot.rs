@@ -0,0 +1,110 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::Size;
+
+/// Resolution a [`Screenshot`] is rendered at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolution {
+    /// Dimensions of the frame
+    #[default]
+    Source,
+    /// Physical size of the widget, as the frame is shown on screen
+    Display,
+}
+
+/// RGBA pixels of a rendered frame, row by row without padding
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub dimensions: Size<u32>,
+    pub rgba: Vec<u8>,
+}
+
+/// Future resolving to the [`Snapshot`] requested with [`Program::screenshot`]
+///
+/// The frame is rendered the next time the [`Program`] is drawn and read back once the
+/// GPU is done with it. Resolves to `None` if the request is dropped before that, e.g.
+/// because the [`Program`] is dropped or isn't drawn by the wgpu renderer.
+///
+/// [`Program`]: crate::Program
+/// [`Program::screenshot`]: crate::Program::screenshot
+#[derive(Debug)]
+pub struct Screenshot {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Future for Screenshot {
+    type Output = Option<Snapshot>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Ok(mut shared) = self.shared.lock() else {
+            return Poll::Ready(None);
+        };
+
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Screenshot {
+    /// Takes the [`Snapshot`] if it was already read back
+    pub(crate) fn ready(self) -> Option<Snapshot> {
+        self.shared.lock().ok()?.result.take().flatten()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    result: Option<Option<Snapshot>>,
+    waker: Option<Waker>,
+}
+
+/// Pending screenshot, resolving its [`Screenshot`] when completed or dropped
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub resolution: Resolution,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Request {
+    pub fn new(resolution: Resolution) -> (Self, Screenshot) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        (
+            Self {
+                resolution,
+                shared: shared.clone(),
+            },
+            Screenshot { shared },
+        )
+    }
+
+    pub fn complete(self, snapshot: Snapshot) {
+        self.resolve(Some(snapshot));
+    }
+
+    fn resolve(&self, result: Option<Snapshot>) {
+        if let Ok(mut shared) = self.shared.lock() {
+            if shared.result.is_none() {
+                shared.result = Some(result);
+            }
+
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        self.resolve(None);
+    }
+}
@@ -2,11 +2,113 @@ struct Uniforms {
     sampling_factor: vec2<f32>,
     size: vec2<f32>,
     scale: vec2<f32>,
+    chroma_offset: vec2<f32>,
+    chroma_filter: u32,
+    deinterlace: u32,
+    first_field: u32,
+    transfer: u32,
+    output_transfer: u32,
+    tone_mapping: u32,
+    peak: f32,
+    gamut: mat3x3<f32>,
+    gamut_mapping: u32,
+    output: u32,
+    brightness: f32,
+    contrast: f32,
+    // columns of a mat2x2, which the GL backend would lay out with a stride of 16 bytes
+    chroma_adjustment: vec4<f32>,
+    gamma: f32,
+    lut_strength: f32,
+    yuv_offset: vec3<f32>,
+    yuv_scale: vec3<f32>,
+    yuv_matrix: mat3x3<f32>,
+    ictcp: u32,
+    // whether frames converted to encoded RGB are decoded to linear when drawn
+    decode: u32,
+    border_width: f32,
+    mask: u32,
+    // bounds of the widget in physical pixels, empty when drawn off screen
+    origin: vec2<f32>,
+    extent: vec2<f32>,
+    // top left, top right, bottom right and bottom left
+    radius: vec4<f32>,
+    border_color: vec4<f32>,
+    opacity: f32,
+    // progress of the cross-fade from the previous frame, 1.0 once it is done
+    fade: f32,
+    // values of the post-processing hook
+    post: array<vec4<f32>, 4>,
+    // normalized chroma of the key color
+    key: vec2<f32>,
+    key_similarity: f32,
+    key_smoothness: f32,
+    key_spill: f32,
+    chroma_key: u32,
+    _padding: vec2<u32>,
+    privacy_regions: u32,
+    regions: array<Region, 16>,
+    // two vertices in each element
+    region_vertices: array<vec4<f32>, 64>,
 }
 
+// Region of the frame hidden for privacy
+struct Region {
+    // left, top, right and bottom edges, relative to the frame
+    bounds: vec4<f32>,
+    color: vec4<f32>,
+    // solid, pixelate or blur
+    mode: u32,
+    // size of the blocks or radius of the blur, in pixels of the frame
+    strength: f32,
+    first_vertex: u32,
+    // vertices of the polygon, 0 for the whole bounds
+    vertices: u32,
+}
+
+struct Lut {
+    cube_min: vec4<f32>,
+    cube_max: vec4<f32>,
+    shaper_min: vec4<f32>,
+    shaper_max: vec4<f32>,
+    cube_size: u32,
+    shaper_size: u32,
+}
+
+const TRANSFER_BT709: u32 = 0u;
+const TRANSFER_SRGB: u32 = 1u;
+const TRANSFER_LINEAR: u32 = 2u;
+const TRANSFER_PQ: u32 = 3u;
+const TRANSFER_HLG: u32 = 4u;
+
+// encoding expected by the target
+const OUTPUT_ENCODED: u32 = 0u;
+const OUTPUT_LINEAR: u32 = 1u;
+const OUTPUT_EXTENDED: u32 = 2u;
+
+// cd/m² of SDR reference white, which linear values are relative to
+const REFERENCE_WHITE: f32 = 203.0;
+
+// BT.2100 LMS to BT.2020 RGB, for frames encoded as ICtCp
+const LMS_TO_RGB: mat3x3<f32> = mat3x3<f32>(
+    vec3<f32>(3.436607, -0.791330, -0.025950),
+    vec3<f32>(-2.506452, 1.983600, -0.098914),
+    vec3<f32>(0.069845, -0.192271, 1.124864),
+);
+
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var mask_texture: texture_2d<f32>;
 @group(1) @binding(0) var yuv_texture: texture_2d_array<f32>;
 @group(1) @binding(1) var yuv_sampler: sampler;
+@group(1) @binding(2) var previous_texture: texture_2d_array<f32>;
+@group(2) @binding(0) var lut_cube: texture_3d<f32>;
+@group(2) @binding(1) var lut_shaper: texture_2d<f32>;
+@group(2) @binding(2) var<uniform> lut: Lut;
+// frames converted once by a compute pass, sampled when drawn
+@group(3) @binding(0) var converted_texture: texture_2d<f32>;
+@group(3) @binding(1) var converted_rgba8: texture_storage_2d<rgba8unorm, write>;
+@group(3) @binding(2) var converted_rgba16f: texture_storage_2d<rgba16float, write>;
+// bitmaps drawn over the frame, bound alongside the sampler in place of the planes
+@group(1) @binding(3) var overlay_texture: texture_2d<f32>;
 
 struct VertexInput {
     @builtin(vertex_index) vertex_index: u32,
@@ -39,16 +141,824 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    // https://learn.microsoft.com/en-us/windows/win32/medfound/recommended-8-bit-yuv-formats-for-video-rendering#converting-8-bit-yuv-to-rgb888
-    let c = textureSample(yuv_texture, yuv_sampler, input.uv, 0).x - 0.062745;
-    let d = textureSample(yuv_texture, yuv_sampler, input.uv / uniforms.sampling_factor, 1).x - 0.5;
-    let e = textureSample(yuv_texture, yuv_sampler, input.uv / uniforms.sampling_factor, 2).x - 0.5;
+    let gradients = Gradients(dpdx(input.uv), dpdy(input.uv));
+
+    let rgba = fade(convert(input.uv, gradients), input.uv, gradients);
+
+    return shape(post(rgba, input.uv), input.position.xy);
+}
+
+@fragment
+fn fs_converted(input: VertexOutput) -> @location(0) vec4<f32> {
+    let gradients = Gradients(dpdx(input.uv), dpdy(input.uv));
+    var rgba = textureSample(converted_texture, yuv_sampler, input.uv);
+
+    if uniforms.decode == 1u {
+        rgba = vec4<f32>(to_linear(rgba.rgb, TRANSFER_SRGB), rgba.a);
+    }
+
+    return shape(post(fade(rgba, input.uv, gradients), input.uv), input.position.xy);
+}
+
+// Blends the previous frame into `rgba` while cross-fading
+fn fade(rgba: vec4<f32>, uv: vec2<f32>, gradients: Gradients) -> vec4<f32> {
+    // the previous frame isn't redacted, so it is left out of privacy regions
+    if uniforms.fade >= 1.0 || privacy_region(uv) >= 0 {
+        return rgba;
+    }
+
+    return mix(convert_previous(uv, gradients), rgba, uniforms.fade);
+}
+
+// Rounds the corners of the widget, draws its border and applies the alpha mask at
+// `position`, given in physical pixels
+fn shape(rgba: vec4<f32>, position: vec2<f32>) -> vec4<f32> {
+    let mask_uv = (position - uniforms.origin) / max(uniforms.extent, vec2<f32>(1.0));
+    let mask = textureSampleLevel(mask_texture, yuv_sampler, mask_uv, 0.0).r;
+
+    if uniforms.extent.x <= 0.0 || uniforms.extent.y <= 0.0 {
+        return rgba;
+    }
+
+    let half = 0.5 * uniforms.extent;
+    let p = position - uniforms.origin - half;
+
+    // radius of the corner of the quadrant `position` is in
+    let corners = select(uniforms.radius.wz, uniforms.radius.xy, p.y < 0.0);
+    let radius = min(select(corners.y, corners.x, p.x < 0.0), min(half.x, half.y));
+
+    // signed distance to the rounded rectangle, negative inside
+    let q = abs(p) - half + radius;
+    let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+
+    var color = rgba;
+    if uniforms.border_width > 0.0 {
+        let inside = clamp(0.5 - (distance + uniforms.border_width), 0.0, 1.0);
+        color = mix(uniforms.border_color, rgba, inside);
+    }
+
+    var alpha = color.a * clamp(0.5 - distance, 0.0, 1.0);
+    if uniforms.mask == 1u {
+        alpha *= mask;
+    }
+
+    return vec4<f32>(color.rgb, alpha * uniforms.opacity);
+}
+
+// Bitmap drawn over the frame, e.g. a subtitle
+struct OverlayInput {
+    @builtin(vertex_index) vertex_index: u32,
+    // left, bottom, width and height in clip space
+    @location(0) bounds: vec4<f32>,
+}
+
+struct OverlayOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_overlay(input: OverlayInput) -> OverlayOutput {
+    var out: OverlayOutput;
+
+    let v_pos = vertex_position(input.vertex_index);
+    out.position = vec4<f32>(input.bounds.xy + v_pos * input.bounds.zw, 0.0, 1.0);
+    out.uv = vec2<f32>(v_pos.x, 1.0 - v_pos.y);
+
+    return out;
+}
+
+@fragment
+fn fs_overlay(input: OverlayOutput) -> @location(0) vec4<f32> {
+    var rgba = textureSample(overlay_texture, yuv_sampler, input.uv);
+
+    // bitmaps are given in sRGB, like the frames written to encoded targets
+    if uniforms.output != OUTPUT_ENCODED {
+        rgba = vec4<f32>(to_linear(rgba.rgb, TRANSFER_SRGB), rgba.a);
+    }
+
+    return shape(rgba, input.position.xy);
+}
+
+// Tile of a video wall, whose planes are layers of the texture array
+struct TileInput {
+    @builtin(vertex_index) vertex_index: u32,
+    // left, bottom, width and height in clip space
+    @location(0) bounds: vec4<f32>,
+    @location(1) layer: u32,
+    @location(2) offset: vec3<f32>,
+    @location(3) scale: vec3<f32>,
+    @location(4) matrix_x: vec3<f32>,
+    @location(5) matrix_y: vec3<f32>,
+    @location(6) matrix_z: vec3<f32>,
+    @location(7) chroma: vec4<f32>,
+    // brightness, contrast and gamma
+    @location(8) luma: vec3<f32>,
+}
+
+struct TileOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) @interpolate(flat) offset: vec3<f32>,
+    @location(3) @interpolate(flat) scale: vec3<f32>,
+    @location(4) @interpolate(flat) matrix_x: vec3<f32>,
+    @location(5) @interpolate(flat) matrix_y: vec3<f32>,
+    @location(6) @interpolate(flat) matrix_z: vec3<f32>,
+    @location(7) @interpolate(flat) chroma: vec4<f32>,
+    @location(8) @interpolate(flat) luma: vec3<f32>,
+}
+
+@vertex
+fn vs_tile(input: TileInput) -> TileOutput {
+    var out: TileOutput;
+
+    let v_pos = vertex_position(input.vertex_index);
+    out.position = vec4<f32>(input.bounds.xy + v_pos * input.bounds.zw, 0.0, 1.0);
+    out.uv = vec2<f32>(v_pos.x, 1.0 - v_pos.y);
+    out.layer = input.layer;
+    out.offset = input.offset;
+    out.scale = input.scale;
+    out.matrix_x = input.matrix_x;
+    out.matrix_y = input.matrix_y;
+    out.matrix_z = input.matrix_z;
+    out.chroma = input.chroma;
+    out.luma = input.luma;
+
+    return out;
+}
+
+@fragment
+fn fs_tile(input: TileOutput) -> @location(0) vec4<f32> {
+    let color = Color(
+        input.offset,
+        input.scale,
+        mat3x3<f32>(input.matrix_x, input.matrix_y, input.matrix_z),
+        mat2x2<f32>(input.chroma.xy, input.chroma.zw),
+        input.luma.x,
+        input.luma.y,
+        input.luma.z,
+    );
+
+    return convert_planes(input.uv, Gradients(dpdx(input.uv), dpdy(input.uv)), i32(input.layer), color);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_rgba8(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(converted_rgba8);
+
+    if all(id.xy < dimensions) {
+        textureStore(converted_rgba8, id.xy, convert_texel(id.xy, dimensions));
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_rgba16f(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(converted_rgba16f);
+
+    if all(id.xy < dimensions) {
+        textureStore(converted_rgba16f, id.xy, convert_texel(id.xy, dimensions));
+    }
+}
+
+// Converts the frame at the center of a texel of the converted texture, which has the
+// dimensions of the frame
+fn convert_texel(texel: vec2<u32>, dimensions: vec2<u32>) -> vec4<f32> {
+    let size = vec2<f32>(dimensions);
+
+    return convert(
+        (vec2<f32>(texel) + 0.5) / size,
+        Gradients(vec2<f32>(1.0 / size.x, 0.0), vec2<f32>(0.0, 1.0 / size.y)),
+    );
+}
+
+// Derivatives of texture coordinates across a pixel of the target
+struct Gradients {
+    ddx: vec2<f32>,
+    ddy: vec2<f32>,
+}
+
+// Converts the frame at `uv` to RGB encoded for the target, hiding privacy regions
+fn convert(uv: vec2<f32>, gradients: Gradients) -> vec4<f32> {
+    let index = privacy_region(uv);
+
+    if index < 0 {
+        return convert_planes(uv, gradients, 0, uniform_color());
+    }
+
+    return redact(uv, gradients, uniforms.regions[index]);
+}
+
+// Index of the privacy region at `uv`, the last one where they overlap, or -1 for none
+fn privacy_region(uv: vec2<f32>) -> i32 {
+    for (var i = i32(uniforms.privacy_regions) - 1; i >= 0; i--) {
+        let region = uniforms.regions[i];
+
+        if all(uv >= region.bounds.xy) && all(uv <= region.bounds.zw)
+            && (region.vertices == 0u || in_polygon(uv, region)) {
+            return i;
+        }
+    }
+
+    return -1;
+}
+
+// Even-odd rule for the polygon of `region`
+fn in_polygon(uv: vec2<f32>, region: Region) -> bool {
+    var inside = false;
+    var previous = region_vertex(region.first_vertex + region.vertices - 1u);
+
+    for (var i = 0u; i < region.vertices; i++) {
+        let current = region_vertex(region.first_vertex + i);
+
+        if (current.y > uv.y) != (previous.y > uv.y) {
+            let x = current.x + (uv.y - current.y) / (previous.y - current.y) * (previous.x - current.x);
+
+            if uv.x < x {
+                inside = !inside;
+            }
+        }
+
+        previous = current;
+    }
+
+    return inside;
+}
+
+fn region_vertex(index: u32) -> vec2<f32> {
+    let pair = uniforms.region_vertices[index / 2u];
+
+    return select(pair.xy, pair.zw, (index & 1u) == 1u);
+}
+
+// Hides the frame at `uv` within `region`, the YUV of the frame is pixelated or blurred
+// before it is converted
+fn redact(uv: vec2<f32>, gradients: Gradients, region: Region) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(yuv_texture));
+
+    if region.mode == 1u {
+        let block = region.strength / size;
+        let center = (floor(uv / block) + 0.5) * block;
+
+        return convert_planes(center, gradients, 0, uniform_color());
+    } else if region.mode == 2u {
+        return to_rgba(blur(uv, region.strength / size), uniform_color());
+    }
+
+    return region.color;
+}
+
+// Gaussian blur of the planes at `uv`, with a standard deviation of half the `radius`
+fn blur(uv: vec2<f32>, radius: vec2<f32>) -> vec3<f32> {
+    // taps a third of the radius apart, sampled from the mipmaps where there are any
+    let step = radius / 3.0;
+    let gradients = Gradients(vec2<f32>(step.x, 0.0), vec2<f32>(0.0, step.y));
+
+    var sum = vec3<f32>(0.0);
+    var weights = 0.0;
+
+    for (var y = -3; y <= 3; y++) {
+        for (var x = -3; x <= 3; x++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / 4.5);
+
+            sum += weight * sample_bilinear(yuv_texture, uv + offset * step, gradients);
+            weights += weight;
+        }
+    }
+
+    return sum / weights;
+}
+
+// Conversion of samples to non-linear RGB, which the tiles of a video wall set on their own
+struct Color {
+    offset: vec3<f32>,
+    scale: vec3<f32>,
+    matrix: mat3x3<f32>,
+    chroma: mat2x2<f32>,
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+}
+
+fn uniform_color() -> Color {
+    return Color(
+        uniforms.yuv_offset,
+        uniforms.yuv_scale,
+        uniforms.yuv_matrix,
+        chroma_adjustment(),
+        uniforms.brightness,
+        uniforms.contrast,
+        uniforms.gamma,
+    );
+}
+
+// Converts the frame whose planes start at `layer` at `uv`
+fn convert_planes(uv: vec2<f32>, gradients: Gradients, layer: i32, color: Color) -> vec4<f32> {
+    let sample = vec3<f32>(
+        sample_plane(uv, layer, vec2<f32>(1.0), gradients),
+        sample_chroma(uv + uniforms.chroma_offset, gradients, layer + 1),
+    );
+
+    return to_rgba(sample, color);
+}
+
+// Converts the previous frame at `uv`, which is only shown while cross-fading, so its
+// chroma is sampled bilinearly and it isn't deinterlaced
+fn convert_previous(uv: vec2<f32>, gradients: Gradients) -> vec4<f32> {
+    return to_rgba(sample_bilinear(previous_texture, uv, gradients), uniform_color());
+}
+
+// Samples the planes of `texture` at `uv` with bilinear chroma and without deinterlacing
+fn sample_bilinear(texture: texture_2d_array<f32>, uv: vec2<f32>, gradients: Gradients) -> vec3<f32> {
+    let region = 1.0 / uniforms.sampling_factor;
+    let size = vec2<f32>(textureDimensions(texture)) * region;
+    let chroma_uv = clamp(uv + uniforms.chroma_offset, 0.5 / size, 1.0 - 0.5 / size) * region;
+    let ddx = gradients.ddx * region;
+    let ddy = gradients.ddy * region;
+
+    return vec3<f32>(
+        textureSampleGrad(texture, yuv_sampler, uv, 0, gradients.ddx, gradients.ddy).x,
+        textureSampleGrad(texture, yuv_sampler, chroma_uv, 1, ddx, ddy).x,
+        textureSampleGrad(texture, yuv_sampler, chroma_uv, 2, ddx, ddy).x,
+    );
+}
+
+// Converts samples of the planes to RGB encoded for the target
+fn to_rgba(sample: vec3<f32>, color: Color) -> vec4<f32> {
+    // luma from 0.0 to 1.0 and chroma centered on 0.0
+    let yuv = (sample - color.offset) * color.scale;
+    let adjusted = vec3<f32>(adjust_luma(yuv.x, color), color.chroma * yuv.yz);
+
+    let rgb = from_lms(clamp(color.matrix * adjusted, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let keyed = chroma_key(rgb, yuv.yz);
+
+    return vec4<f32>(to_output(apply_lut(pow(keyed.rgb, vec3<f32>(1.0 / color.gamma)))), keyed.a);
+}
+
+// Keys out colors whose chroma is close to the key color, and desaturates the spill of
+// the key color onto the colors which remain
+fn chroma_key(rgb: vec3<f32>, chroma: vec2<f32>) -> vec4<f32> {
+    if uniforms.chroma_key == 0u {
+        return vec4<f32>(rgb, 1.0);
+    }
+
+    let distance = distance(chroma, uniforms.key) - uniforms.key_similarity;
+    let alpha = pow(clamp(distance / max(uniforms.key_smoothness, 1e-4), 0.0, 1.0), 1.5);
+    let spill = pow(clamp(distance / max(uniforms.key_spill, 1e-4), 0.0, 1.0), 1.5);
+
+    let gray = vec3<f32>(dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722)));
+
+    return vec4<f32>(mix(gray, rgb, spill), alpha);
+}
+
+fn apply_lut(rgb: vec3<f32>) -> vec3<f32> {
+    if uniforms.lut_strength <= 0.0 || (lut.cube_size == 0u && lut.shaper_size == 0u) {
+        return rgb;
+    }
+
+    var graded = rgb;
+
+    if lut.shaper_size > 0u {
+        graded = lut_1d(graded);
+    }
+
+    if lut.cube_size > 0u {
+        graded = lut_3d(graded);
+    }
+
+    return mix(rgb, graded, uniforms.lut_strength);
+}
+
+// Linear interpolation of each channel in the 1D table
+fn lut_1d(rgb: vec3<f32>) -> vec3<f32> {
+    let last = f32(lut.shaper_size - 1u);
+    let position = clamp((rgb - lut.shaper_min.rgb) / (lut.shaper_max.rgb - lut.shaper_min.rgb), vec3<f32>(0.0), vec3<f32>(1.0)) * last;
+    let lower = min(floor(position), vec3<f32>(last - 1.0));
+    let t = position - lower;
+
+    var out: vec3<f32>;
+    for (var i = 0; i < 3; i++) {
+        let a = shaper_entry(i32(lower[i]))[i];
+        let b = shaper_entry(i32(lower[i]) + 1)[i];
+        out[i] = mix(a, b, t[i]);
+    }
+
+    return out;
+}
+
+// Entry of the 1D table, whose rows wrap at the width of its texture
+fn shaper_entry(index: i32) -> vec4<f32> {
+    let width = i32(textureDimensions(lut_shaper).x);
+
+    return textureLoad(lut_shaper, vec2<i32>(index % width, index / width), 0);
+}
+
+// Tetrahedral interpolation in the 3D table
+fn lut_3d(rgb: vec3<f32>) -> vec3<f32> {
+    let last = f32(lut.cube_size - 1u);
+    let position = clamp((rgb - lut.cube_min.rgb) / (lut.cube_max.rgb - lut.cube_min.rgb), vec3<f32>(0.0), vec3<f32>(1.0)) * last;
+    let base = min(floor(position), vec3<f32>(last - 1.0));
+    let f = position - base;
+    let texel = vec3<i32>(base);
+
+    let c000 = textureLoad(lut_cube, texel, 0).rgb;
+    let c111 = textureLoad(lut_cube, texel + vec3<i32>(1, 1, 1), 0).rgb;
+
+    // each ordering of the fractions selects one of the six tetrahedra in the cell
+    if f.r > f.g {
+        if f.g > f.b {
+            let c100 = textureLoad(lut_cube, texel + vec3<i32>(1, 0, 0), 0).rgb;
+            let c110 = textureLoad(lut_cube, texel + vec3<i32>(1, 1, 0), 0).rgb;
+            return (1.0 - f.r) * c000 + (f.r - f.g) * c100 + (f.g - f.b) * c110 + f.b * c111;
+        } else if f.r > f.b {
+            let c100 = textureLoad(lut_cube, texel + vec3<i32>(1, 0, 0), 0).rgb;
+            let c101 = textureLoad(lut_cube, texel + vec3<i32>(1, 0, 1), 0).rgb;
+            return (1.0 - f.r) * c000 + (f.r - f.b) * c100 + (f.b - f.g) * c101 + f.g * c111;
+        } else {
+            let c001 = textureLoad(lut_cube, texel + vec3<i32>(0, 0, 1), 0).rgb;
+            let c101 = textureLoad(lut_cube, texel + vec3<i32>(1, 0, 1), 0).rgb;
+            return (1.0 - f.b) * c000 + (f.b - f.r) * c001 + (f.r - f.g) * c101 + f.g * c111;
+        }
+    } else {
+        if f.b > f.g {
+            let c001 = textureLoad(lut_cube, texel + vec3<i32>(0, 0, 1), 0).rgb;
+            let c011 = textureLoad(lut_cube, texel + vec3<i32>(0, 1, 1), 0).rgb;
+            return (1.0 - f.b) * c000 + (f.b - f.g) * c001 + (f.g - f.r) * c011 + f.r * c111;
+        } else if f.b > f.r {
+            let c010 = textureLoad(lut_cube, texel + vec3<i32>(0, 1, 0), 0).rgb;
+            let c011 = textureLoad(lut_cube, texel + vec3<i32>(0, 1, 1), 0).rgb;
+            return (1.0 - f.g) * c000 + (f.g - f.b) * c010 + (f.b - f.r) * c011 + f.r * c111;
+        } else {
+            let c010 = textureLoad(lut_cube, texel + vec3<i32>(0, 1, 0), 0).rgb;
+            let c110 = textureLoad(lut_cube, texel + vec3<i32>(1, 1, 0), 0).rgb;
+            return (1.0 - f.g) * c000 + (f.g - f.r) * c010 + (f.r - f.b) * c110 + f.b * c111;
+        }
+    }
+}
+
+fn chroma_adjustment() -> mat2x2<f32> {
+    return mat2x2<f32>(uniforms.chroma_adjustment.xy, uniforms.chroma_adjustment.zw);
+}
+
+// Applies brightness and contrast to normalized luma
+fn adjust_luma(luma: f32, color: Color) -> f32 {
+    return (luma - 0.5) * color.contrast + 0.5 + color.brightness;
+}
+
+// Converts the non-linear RGB of the frame to the encoding of the target
+fn to_output(rgb: vec3<f32>) -> vec3<f32> {
+    // floating point targets take linear light beyond SDR white as it is
+    if uniforms.output == OUTPUT_EXTENDED && uniforms.transfer >= TRANSFER_LINEAR {
+        return map_gamut(to_linear(rgb, uniforms.transfer));
+    }
+
+    let encoded = to_display(rgb);
+
+    // sRGB targets encode on write, so undo the encoding the display would apply
+    if uniforms.output != OUTPUT_ENCODED {
+        return to_linear(encoded, TRANSFER_SRGB);
+    }
+
+    return encoded;
+}
+
+// Converts the non-linear RGB of the frame to SDR display encoding
+fn to_display(rgb: vec3<f32>) -> vec3<f32> {
+    if uniforms.transfer == uniforms.output_transfer && uniforms.gamut_mapping == 0u {
+        return rgb;
+    }
+
+    var linear = to_linear(rgb, uniforms.transfer);
+
+    if uniforms.transfer == TRANSFER_PQ || uniforms.transfer == TRANSFER_HLG {
+        linear = tone_map(linear);
+    }
+
+    return from_linear(map_gamut(linear), uniforms.output_transfer);
+}
+
+fn map_gamut(linear: vec3<f32>) -> vec3<f32> {
+    if uniforms.gamut_mapping == 0u {
+        return linear;
+    }
+
+    let converted = uniforms.gamut * linear;
+
+    if uniforms.gamut_mapping == 2u {
+        return compress_gamut(converted);
+    }
+
+    return converted;
+}
+
+// Smoothly compresses the distance of each component from the achromatic axis,
+// so that colors beyond the gamut boundary approach it instead of being clipped
+//
+// Like the ACES reference gamut compression, distances from the threshold up to the limit
+// are compressed into the gamut (1.0) and further ones are clipped. Colors within the gamut
+// beyond the threshold are desaturated slightly, which keeps gradients across the boundary
+// smooth.
+fn compress_gamut(linear: vec3<f32>) -> vec3<f32> {
+    let achromatic = max(max(linear.r, linear.g), linear.b);
+
+    if achromatic <= 0.0 {
+        return linear;
+    }
+
+    let threshold = 0.8;
+    let limit = 1.2;
+    let power = 1.2;
+    // scale of the curve which maps the limit to the gamut boundary
+    let scale = (limit - threshold)
+        / pow(pow((1.0 - threshold) / (limit - threshold), -power) - 1.0, 1.0 / power);
+
+    let distance = (achromatic - linear) / achromatic;
+    let excess = max(distance - threshold, vec3<f32>(0.0)) / scale;
+    let compressed = select(
+        distance,
+        threshold + scale * excess / pow(1.0 + pow(excess, vec3<f32>(power)), vec3<f32>(1.0 / power)),
+        distance > vec3<f32>(threshold),
+    );
+
+    return achromatic - compressed * achromatic;
+}
+
+// EOTF of `transfer`, with SDR reference white at 1.0
+fn to_linear(rgb: vec3<f32>, transfer: u32) -> vec3<f32> {
+    if transfer == TRANSFER_BT709 {
+        return pow(rgb, vec3<f32>(2.4));
+    } else if transfer == TRANSFER_SRGB {
+        return select(pow((rgb + 0.055) / 1.055, vec3<f32>(2.4)), rgb / 12.92, rgb <= vec3<f32>(0.04045));
+    } else if transfer == TRANSFER_PQ {
+        return vec3<f32>(pq_decode(rgb.r), pq_decode(rgb.g), pq_decode(rgb.b)) / REFERENCE_WHITE;
+    } else if transfer == TRANSFER_HLG {
+        return hlg_ootf(hlg_inverse_oetf(rgb)) / REFERENCE_WHITE;
+    }
+
+    return rgb;
+}
+
+// Inverse EOTF of (SDR) `transfer`
+fn from_linear(linear: vec3<f32>, transfer: u32) -> vec3<f32> {
+    let rgb = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    if transfer == TRANSFER_BT709 {
+        return pow(rgb, vec3<f32>(1.0 / 2.4));
+    } else if transfer == TRANSFER_SRGB {
+        return select(1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055, rgb * 12.92, rgb <= vec3<f32>(0.0031308));
+    }
+
+    return rgb;
+}
+
+// SMPTE ST 2084 signal to cd/m²
+fn pq_decode(signal: f32) -> f32 {
+    let p = pow(signal, 1.0 / 78.84375);
+
+    return 10000.0 * pow(max(p - 0.8359375, 0.0) / (18.8515625 - 18.6875 * p), 1.0 / 0.1593017578125);
+}
+
+// cd/m² to SMPTE ST 2084 signal
+fn pq_encode(luminance: f32) -> f32 {
+    let y = pow(clamp(luminance / 10000.0, 0.0, 1.0), 0.1593017578125);
+
+    return pow((0.8359375 + 18.8515625 * y) / (1.0 + 18.6875 * y), 78.84375);
+}
+
+// ICtCp frames are converted to non-linear LMS, which is converted to RGB with the same
+// transfer function, so that adjustments and LUTs apply to RGB like for other frames
+fn from_lms(signal: vec3<f32>) -> vec3<f32> {
+    if uniforms.ictcp == 0u {
+        return signal;
+    }
+
+    if uniforms.transfer == TRANSFER_PQ {
+        let rgb = LMS_TO_RGB * vec3<f32>(pq_decode(signal.r), pq_decode(signal.g), pq_decode(signal.b));
+
+        return vec3<f32>(pq_encode(rgb.r), pq_encode(rgb.g), pq_encode(rgb.b));
+    } else if uniforms.transfer == TRANSFER_HLG {
+        return hlg_oetf(LMS_TO_RGB * hlg_inverse_oetf(signal));
+    }
+
+    return signal;
+}
+
+// BT.2100 HLG signal to normalized scene light
+fn hlg_inverse_oetf(signal: vec3<f32>) -> vec3<f32> {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+
+    return select((exp((signal - c) / a) + b) / 12.0, signal * signal / 3.0, signal <= vec3<f32>(0.5));
+}
+
+// Normalized scene light to BT.2100 HLG signal
+fn hlg_oetf(scene: vec3<f32>) -> vec3<f32> {
+    let a = 0.17883277;
+    let b = 0.28466892;
+    let c = 0.55991073;
+    let e = max(scene, vec3<f32>(0.0));
+
+    return select(a * log(max(12.0 * e - b, vec3<f32>(1e-6))) + c, sqrt(3.0 * e), e <= vec3<f32>(1.0 / 12.0));
+}
+
+// BT.2100 HLG OOTF for a display of the peak luminance, in cd/m²
+fn hlg_ootf(scene: vec3<f32>) -> vec3<f32> {
+    let peak = uniforms.peak * REFERENCE_WHITE;
+    let gamma = 1.2 + 0.42 * log2(peak / 1000.0) / log2(10.0);
+    let luminance = dot(scene, vec3<f32>(0.2627, 0.6780, 0.0593));
+
+    return peak * pow(max(luminance, 1e-6), gamma - 1.0) * scene;
+}
+
+// Compresses linear HDR into the SDR range, scaling each color by its largest component
+fn tone_map(linear: vec3<f32>) -> vec3<f32> {
+    let x = max(max(linear.r, linear.g), linear.b);
+    let peak = uniforms.peak;
+
+    if x <= 0.0 {
+        return linear;
+    }
+
+    var mapped: f32;
+    if uniforms.tone_mapping == 0u {
+        mapped = bt2390(x, peak);
+    } else if uniforms.tone_mapping == 1u {
+        mapped = hable(x) / hable(peak);
+    } else if uniforms.tone_mapping == 2u {
+        mapped = x * (1.0 + x / (peak * peak)) / (1.0 + x);
+    } else {
+        mapped = min(x, 1.0);
+    }
+
+    return linear * (mapped / x);
+}
+
+// BT.2390 EETF with a target of SDR reference white
+fn bt2390(x: f32, peak: f32) -> f32 {
+    let source_max = pq_encode(peak * REFERENCE_WHITE);
+    let target_max = pq_encode(REFERENCE_WHITE) / source_max;
+    let knee = 1.5 * target_max - 0.5;
+    let e = min(pq_encode(x * REFERENCE_WHITE) / source_max, 1.0);
+
+    if e <= knee {
+        return x;
+    }
+
+    let t = (e - knee) / (1.0 - knee);
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let spline = (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+        + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+        + (-2.0 * t3 + 3.0 * t2) * target_max;
+
+    return pq_decode(spline * source_max) / REFERENCE_WHITE;
+}
+
+// Hable's filmic curve (Uncharted 2)
+fn hable(x: f32) -> f32 {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+// Samples a plane at `uv`, given in coordinates of the plane, which covers `region` of `layer`
+fn sample_plane(uv: vec2<f32>, layer: i32, region: vec2<f32>, gradients: Gradients) -> f32 {
+    let ddx = gradients.ddx * region;
+    let ddy = gradients.ddy * region;
+
+    if uniforms.deinterlace == 0u {
+        return textureSampleGrad(yuv_texture, yuv_sampler, uv * region, layer, ddx, ddy).x;
+    }
+
+    let rows = f32(textureDimensions(yuv_texture).y) * region.y;
+    let lines = Lines(uv.x, layer, region, rows, ddx, ddy);
+    let row = uv.y * rows - 0.5;
+
+    if uniforms.deinterlace == 1u {
+        // bob: interpolate between the lines of the first field
+        let first = f32(uniforms.first_field);
+        let field_row = (row - first) / 2.0;
+        let above = 2.0 * floor(field_row) + first;
+
+        return mix(line(lines, above), line(lines, above + 2.0), fract(field_row));
+    } else if uniforms.deinterlace == 2u {
+        // linear blend: 1-2-1 filter of neighbouring lines, each tap averaging two lines
+        let nearest = floor(row + 0.5);
+
+        return 0.5 * (line(lines, nearest - 0.5) + line(lines, nearest + 0.5));
+    }
+
+    let above = floor(row);
+
+    return mix(adaptive_line(lines, above), adaptive_line(lines, above + 1.0), fract(row));
+}
+
+// Lines of a plane at a fixed horizontal position
+struct Lines {
+    x: f32,
+    layer: i32,
+    region: vec2<f32>,
+    rows: f32,
+    ddx: vec2<f32>,
+    ddy: vec2<f32>,
+}
+
+fn line_uv(lines: Lines, row: f32) -> vec2<f32> {
+    let y = (clamp(row, 0.0, lines.rows - 1.0) + 0.5) / lines.rows;
+
+    return vec2<f32>(lines.x, y) * lines.region;
+}
+
+fn line(lines: Lines, row: f32) -> f32 {
+    return textureSampleGrad(
+        yuv_texture, yuv_sampler, line_uv(lines, row), lines.layer, lines.ddx, lines.ddy
+    ).x;
+}
+
+fn previous_line(lines: Lines, row: f32) -> f32 {
+    return textureSampleGrad(
+        previous_texture, yuv_sampler, line_uv(lines, row), lines.layer, lines.ddx, lines.ddy
+    ).x;
+}
+
+// Line at (integral) `row`, with lines of the second field replaced where they moved
+fn adaptive_line(lines: Lines, row: f32) -> f32 {
+    let current = line(lines, row);
+
+    if (u32(clamp(row, 0.0, lines.rows - 1.0)) & 1u) == uniforms.first_field {
+        return current;
+    }
+
+    let above = line(lines, row - 1.0);
+    let below = line(lines, row + 1.0);
+    let motion = max(
+        abs(current - previous_line(lines, row)),
+        0.5 * (abs(above - previous_line(lines, row - 1.0)) + abs(below - previous_line(lines, row + 1.0))),
+    );
+
+    return mix(current, 0.5 * (above + below), smoothstep(0.01, 0.05, motion));
+}
+
+// Samples the U and V planes, at `layer` and the one after it, at `uv`, given in luma
+// texture coordinates
+fn sample_chroma(uv: vec2<f32>, gradients: Gradients, layer: i32) -> vec2<f32> {
+    // the chroma planes only occupy part of their (luma sized) layers
+    let size = vec2<f32>(textureDimensions(yuv_texture)) / uniforms.sampling_factor;
+    let clamped = clamp(uv, 0.5 / size, 1.0 - 0.5 / size);
+
+    let region = 1.0 / uniforms.sampling_factor;
+    let filtered = vec2<f32>(
+        sample_plane(clamped, layer, region, gradients),
+        sample_plane(clamped, layer + 1, region, gradients),
+    );
+
+    // bicubic upsampling only helps when magnifying, otherwise prefer the mipmaps
+    let footprint = (abs(gradients.ddx) + abs(gradients.ddy)) * size;
+    let magnified = max(footprint.x, footprint.y) <= 1.0;
+    if uniforms.chroma_filter == 1u && uniforms.deinterlace == 0u && magnified {
+        return chroma_bicubic(clamped * size - 0.5, vec2<i32>(size) - 1, layer);
+    }
+
+    return filtered;
+}
+
+// Catmull-Rom interpolation of the chroma planes at `position`, given in chroma texels
+fn chroma_bicubic(position: vec2<f32>, max_texel: vec2<i32>, layer: i32) -> vec2<f32> {
+    let base = floor(position);
+    let wx = catmull_rom(position.x - base.x);
+    let wy = catmull_rom(position.y - base.y);
+
+    var chroma = vec2<f32>(0.0);
+    for (var j = 0; j < 4; j++) {
+        for (var i = 0; i < 4; i++) {
+            let texel = clamp(vec2<i32>(base) + vec2<i32>(i - 1, j - 1), vec2<i32>(0), max_texel);
+
+            chroma += wx[i] * wy[j] * vec2<f32>(
+                textureLoad(yuv_texture, texel, layer, 0).x,
+                textureLoad(yuv_texture, texel, layer + 1, 0).x,
+            );
+        }
+    }
+
+    return chroma;
+}
+
+fn catmull_rom(t: f32) -> vec4<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
 
     return vec4<f32>(
-        clamp(c + 1.596027 * e, 0.0, 1.0),
-        clamp(c - 0.391762 * d - 0.812968 * e, 0.0, 1.0),
-        clamp(c + 2.017232 * d, 0.0, 1.0),
-        1.0
+        -0.5 * t3 + t2 - 0.5 * t,
+        1.5 * t3 - 2.5 * t2 + 1.0,
+        -1.5 * t3 + 2.0 * t2 + 0.5 * t,
+        0.5 * t3 - 0.5 * t2,
     );
 }
 
@@ -0,0 +1,390 @@
+use iced::mouse;
+use iced::widget::shader;
+use iced::Rectangle;
+use shader::wgpu;
+use thiserror::Error;
+
+use super::pipeline::{Tile as Instance, Uniforms, Wall};
+use super::{ChromaFilter, Frame, GamutMapping, PictureAdjustments, Settings, ToneMapping};
+use crate::{Size, WeakData, Yuv};
+
+/// Most tiles of a [`VideoWall`]
+///
+/// The three planes of each tile take a layer of one texture array, and devices only have
+/// to support 256 layers.
+pub const MAX_WALL_TILES: usize = 85;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WallError {
+    #[error("a video wall holds at most {MAX_WALL_TILES} tiles")]
+    TooManyTiles,
+    #[error("expected a frame of {expected:?} like the other tiles, found {found:?}")]
+    Dimensions {
+        expected: Size<u32>,
+        found: Size<u32>,
+    },
+    #[error("expected a frame with the chroma subsampling of the other tiles")]
+    Subsampling,
+}
+
+/// Draws many frames (e.g. the cameras of a security grid) as tiles of one widget, with
+/// a single instanced draw
+///
+/// The frames of all tiles share their dimensions and chroma subsampling, and there are
+/// at most [`MAX_WALL_TILES`] of them. The transfer function and primaries of the first
+/// tile apply to every tile, while the matrix, range and [`PictureAdjustments`] are set
+/// per tile.
+#[derive(Default)]
+pub struct VideoWall {
+    /// Settings each tile starts out with
+    settings: Settings,
+    tiles: Vec<Tile>,
+}
+
+struct Tile {
+    /// Bounds relative to the wall, from 0.0 to 1.0
+    bounds: Rectangle,
+    frame: Frame,
+    source: WeakData,
+    sequence: Option<u64>,
+    settings: Settings,
+}
+
+impl VideoWall {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a [`VideoWall`] laying out the frames in a grid of `columns`, row by row.
+    pub fn grid(columns: usize, frames: impl IntoIterator<Item = Yuv>) -> Result<Self, WallError> {
+        let frames: Vec<_> = frames.into_iter().collect();
+        let columns = columns.max(1);
+        let rows = frames.len().div_ceil(columns).max(1);
+
+        frames
+            .into_iter()
+            .enumerate()
+            .try_fold(Self::new(), |wall, (i, yuv)| {
+                let bounds = Rectangle {
+                    x: (i % columns) as f32 / columns as f32,
+                    y: (i / columns) as f32 / rows as f32,
+                    width: 1.0 / columns as f32,
+                    height: 1.0 / rows as f32,
+                };
+
+                wall.tile(bounds, yuv)
+            })
+    }
+
+    /// Adds a tile drawing `yuv` within `bounds`, given relative to the wall from 0.0 to 1.0.
+    pub fn tile(mut self, bounds: Rectangle, yuv: Yuv) -> Result<Self, WallError> {
+        self.push(bounds, yuv)?;
+        Ok(self)
+    }
+
+    /// Sets the [`ChromaFilter`] of every tile.
+    pub fn chroma_filter(mut self, filter: ChromaFilter) -> Self {
+        self.settings.chroma_filter = filter;
+        self.tiles
+            .iter_mut()
+            .for_each(|tile| tile.settings.chroma_filter = filter);
+        self
+    }
+
+    /// Sets the [`ToneMapping`] operator used to render HDR tiles to SDR.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.settings.tone_mapping = tone_mapping;
+        self.tiles
+            .iter_mut()
+            .for_each(|tile| tile.settings.tone_mapping = tone_mapping);
+        self
+    }
+
+    /// Sets how colors outside of the BT.709 / sRGB gamut are handled.
+    pub fn gamut_mapping(mut self, gamut_mapping: GamutMapping) -> Self {
+        self.settings.gamut_mapping = gamut_mapping;
+        self.tiles
+            .iter_mut()
+            .for_each(|tile| tile.settings.gamut_mapping = gamut_mapping);
+        self
+    }
+
+    /// Adds a tile like [`VideoWall::tile`] and returns its index.
+    pub fn push(&mut self, bounds: Rectangle, yuv: Yuv) -> Result<usize, WallError> {
+        if self.tiles.len() == MAX_WALL_TILES {
+            return Err(WallError::TooManyTiles);
+        }
+
+        let mut settings = self.settings;
+        settings.update(&yuv);
+
+        let source = yuv.data.downgrade();
+        let sequence = yuv.sequence;
+        let frame = Frame::new(yuv, None);
+        self.check(&frame, None)?;
+
+        self.tiles.push(Tile {
+            bounds,
+            frame,
+            source,
+            sequence,
+            settings,
+        });
+
+        Ok(self.tiles.len() - 1)
+    }
+
+    /// Checks that `frame` matches the frames of the tiles other than `index`
+    fn check(&self, frame: &Frame, index: Option<usize>) -> Result<(), WallError> {
+        let Some(other) = (0..self.tiles.len())
+            .find(|i| Some(*i) != index)
+            .map(|i| &self.tiles[i].frame.yuv)
+        else {
+            return Ok(());
+        };
+
+        let (expected, found) = (other.dimensions(), frame.yuv.dimensions());
+
+        if expected != found {
+            Err(WallError::Dimensions { expected, found })
+        } else if other.downsampling_factor() != frame.yuv.downsampling_factor() {
+            Err(WallError::Subsampling)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of tiles
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Sets the frame of the tile at `index`, which is uploaded on the next draw unless it
+    /// shares its [`Data`] or its [`Yuv::sequence`] with the previous frame of the tile.
+    ///
+    /// A frame which doesn't match the other tiles is refused, keeping the previous one.
+    ///
+    /// [`Data`]: crate::Data
+    pub fn update_frame(&mut self, index: usize, yuv: Yuv) -> Result<(), WallError> {
+        let Some(tile) = self.tiles.get(index) else {
+            return Ok(());
+        };
+
+        if tile.source.is(&yuv.data) || (yuv.sequence.is_some() && yuv.sequence == tile.sequence) {
+            self.tiles[index].settings.update(&yuv);
+            return Ok(());
+        }
+
+        let source = yuv.data.downgrade();
+        let sequence = yuv.sequence;
+        let mut settings = tile.settings;
+        settings.update(&yuv);
+
+        let frame = Frame::new(yuv, None);
+        self.check(&frame, Some(index))?;
+
+        let tile = &mut self.tiles[index];
+        tile.frame = frame;
+        tile.source = source;
+        tile.sequence = sequence;
+        tile.settings = settings;
+
+        Ok(())
+    }
+
+    /// Moves the tile at `index` to `bounds`, given relative to the wall.
+    pub fn update_bounds(&mut self, index: usize, bounds: Rectangle) {
+        if let Some(tile) = self.tiles.get_mut(index) {
+            tile.bounds = bounds;
+        }
+    }
+
+    /// Updates the [`PictureAdjustments`] of the tile at `index`.
+    pub fn update_adjustments(&mut self, index: usize, adjustments: PictureAdjustments) {
+        if let Some(tile) = self.tiles.get_mut(index) {
+            tile.settings.adjustments = adjustments;
+        }
+    }
+}
+
+impl<Message> shader::Program<Message> for VideoWall {
+    type State = ();
+    type Primitive = Primitive;
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        _cursor: mouse::Cursor,
+        bounds: Rectangle,
+    ) -> Self::Primitive {
+        Primitive {
+            tiles: self
+                .tiles
+                .iter()
+                .map(|tile| (tile.bounds, tile.frame.clone(), tile.settings))
+                .collect(),
+            bounds,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Primitive {
+    tiles: Vec<(Rectangle, Frame, Settings)>,
+    bounds: Rectangle,
+}
+
+impl shader::Primitive for Primitive {
+    fn prepare(
+        &self,
+        format: wgpu::TextureFormat,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bounds: Rectangle,
+        target_size: iced::Size<u32>,
+        scale_factor: f32,
+        storage: &mut shader::Storage,
+    ) {
+        let Some((_, first, settings)) = self.tiles.first() else {
+            return;
+        };
+
+        let target_size =
+            Size::from((target_size.width as f32, target_size.height as f32)) / scale_factor;
+
+        if !storage.has::<Wall>() {
+            storage.store(Wall::new(device, format));
+        }
+
+        let wall = storage.get_mut::<Wall>().expect("yuv wall");
+
+        wall.update_frames(
+            device,
+            queue,
+            self.tiles
+                .iter()
+                .map(|(_, frame, _)| (frame.version, &frame.yuv)),
+        );
+
+        let image_dimensions = Size::from(first.yuv.dimensions());
+
+        wall.update_uniforms(
+            queue,
+            &Uniforms::new(
+                image_dimensions,
+                image_dimensions,
+                first.yuv.downsampling_factor(),
+                image_dimensions,
+                format,
+                settings,
+            ),
+        );
+
+        let instances: Vec<_> = self
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(index, (_, frame, _))| wall.has_frame(*index, frame.version))
+            .map(|(index, (tile, _, settings))| {
+                let tile_bounds = Rectangle {
+                    x: bounds.x + tile.x * bounds.width,
+                    y: bounds.y + tile.y * bounds.height,
+                    width: tile.width * bounds.width,
+                    height: tile.height * bounds.height,
+                };
+
+                Instance::new(index, tile_bounds, target_size, settings)
+            })
+            .collect();
+
+        wall.update_tiles(device, queue, &instances, scale_factor);
+    }
+
+    fn render(
+        &self,
+        storage: &shader::Storage,
+        target: &wgpu::TextureView,
+        _target_size: iced::Size<u32>,
+        _viewport: Rectangle<u32>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if let Some(wall) = storage.get::<Wall>() {
+            wall.render(target, encoder, self.bounds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    #[test]
+    fn grid_lays_out_frames_row_by_row() {
+        let yuv = Yuv {
+            format: Format::Y444,
+            data: vec![0; 12].into(),
+            dimensions: Size {
+                width: 2,
+                height: 2,
+            },
+            ..Default::default()
+        };
+
+        let wall = VideoWall::grid(2, vec![yuv; 5]).expect("matching tiles");
+        let bounds: Vec<_> = wall.tiles.iter().map(|tile| tile.bounds).collect();
+
+        assert_eq!(bounds.len(), 5);
+        assert_eq!(
+            bounds[3],
+            Rectangle {
+                x: 0.5,
+                y: 1.0 / 3.0,
+                width: 0.5,
+                height: 1.0 / 3.0,
+            }
+        );
+        assert_eq!(bounds[4].y, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn mismatched_and_excess_tiles_are_refused() {
+        let yuv = |format, width| Yuv {
+            format,
+            data: vec![0; width as usize * 2 * 3].into(),
+            dimensions: Size { width, height: 2 },
+            ..Default::default()
+        };
+        let mut wall = VideoWall::grid(10, vec![yuv(Format::Y444, 2); 84]).expect("tiles");
+
+        assert_eq!(
+            wall.push(Rectangle::default(), yuv(Format::Y444, 4)),
+            Err(WallError::Dimensions {
+                expected: Size {
+                    width: 2,
+                    height: 2
+                },
+                found: Size {
+                    width: 4,
+                    height: 2
+                },
+            })
+        );
+        assert_eq!(
+            wall.update_frame(0, yuv(Format::I420, 2)),
+            Err(WallError::Subsampling)
+        );
+        assert_eq!(
+            wall.push(Rectangle::default(), yuv(Format::Y444, 2)),
+            Ok(84)
+        );
+        assert_eq!(
+            wall.push(Rectangle::default(), yuv(Format::Y444, 2)),
+            Err(WallError::TooManyTiles)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use iced::advanced::layout;
+use iced::advanced::mouse::Cursor;
+use iced::advanced::renderer;
+use iced::advanced::widget::Tree;
+use iced::advanced::{Layout, Widget};
+use iced::widget::{Image, Shader};
+use iced::{ContentFit, Element, Length, Rectangle, Size};
+
+use crate::Program;
+
+/// Widget drawing a [`Program`] with its shader, or with the frame converted on the
+/// CPU where the renderer can't draw shaders (e.g. tiny-skia)
+pub struct Viewer<'a> {
+    program: &'a Program,
+    width: Length,
+    height: Length,
+}
+
+/// Creates a new [`Viewer`] of the [`Program`].
+pub fn viewer(program: &Program) -> Viewer<'_> {
+    Viewer::new(program)
+}
+
+impl<'a> Viewer<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            width: Length::Fill,
+            height: Length::Fill,
+        }
+    }
+
+    /// Sets the width of the [`Viewer`] boundaries.
+    pub fn width(mut self, width: impl Into<Length>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    /// Sets the height of the [`Viewer`] boundaries.
+    pub fn height(mut self, height: impl Into<Length>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    fn shader<Message: 'a, Theme: 'a>(&self) -> Element<'a, Message, Theme, iced::Renderer> {
+        Element::from(
+            Shader::<Message, &Program>::new(self.program)
+                .width(self.width)
+                .height(self.height),
+        )
+    }
+}
+
+impl<'a, Message, Theme> Widget<Message, Theme, iced::Renderer> for Viewer<'a>
+where
+    Message: 'a,
+    Theme: 'a,
+{
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.shader::<Message, Theme>())]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&[self.shader::<Message, Theme>()]);
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &iced::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.shader::<Message, Theme>()
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut iced::Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: Cursor,
+        viewport: &Rectangle,
+    ) {
+        // images are stateless, so only the shader keeps its tree
+        let empty = Tree::empty();
+        let (content, tree) = if let iced::Renderer::TinySkia(_) = renderer {
+            let Some(handle) = self.program.image() else {
+                return;
+            };
+
+            let image = Image::new(handle)
+                .width(self.width)
+                .height(self.height)
+                .content_fit(ContentFit::Fill);
+
+            (Element::from(image), &empty)
+        } else {
+            (self.shader::<Message, Theme>(), &tree.children[0])
+        };
+
+        content
+            .as_widget()
+            .draw(tree, renderer, theme, style, layout, cursor, viewport)
+    }
+}
+
+impl<'a, Message, Theme> From<Viewer<'a>> for Element<'a, Message, Theme, iced::Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+{
+    fn from(viewer: Viewer<'a>) -> Self {
+        Self::new(viewer)
+    }
+}
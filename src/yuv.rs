@@ -1,16 +1,16 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
 use itertools::Itertools;
 
-#[derive(Debug)]
+use crate::{ColorPrimaries, ColorRange, MatrixCoefficients, TransferFunction};
+
+#[derive(Debug, Clone)]
 /// Supported internal types for rendering
 pub(crate) enum Renderable {
-    Y444 {
-        data: Vec<u8>,
-        dimensions: Size<u32>,
-    },
-    I420 {
-        data: Vec<u8>,
-        dimensions: Size<u32>,
-    },
+    Y444 { data: Data, dimensions: Size<u32> },
+    I420 { data: Data, dimensions: Size<u32> },
 }
 
 impl Renderable {
@@ -50,26 +50,208 @@ impl Renderable {
 }
 
 /// Supported YUV formats
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub enum Format {
+    #[default]
     I420,
     Y444,
+    /// Luma followed by interleaved chroma, which is split into I420 when the frame is
+    /// set, copying it
+    ///
+    /// Decoders which can output I420 instead avoid the copy.
     NV12,
 }
 
+/// Position of the chroma samples relative to the luma samples of a subsampled frame
+///
+/// Variants follow `chroma_sample_loc_type` from ITU-T H.273 and have no effect
+/// on formats without chroma subsampling.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChromaLocation {
+    /// Co-sited horizontally, centered vertically (MPEG-2, H.264 and HEVC default)
+    #[default]
+    Left,
+    /// Centered both horizontally and vertically (JPEG, MPEG-1)
+    Center,
+    /// Co-sited both horizontally and vertically (BT.2020 / BT.2100)
+    TopLeft,
+    /// Centered horizontally, co-sited vertically
+    Top,
+    /// Co-sited horizontally, co-sited with the line below vertically
+    BottomLeft,
+    /// Centered horizontally, co-sited with the line below vertically
+    Bottom,
+}
+
+impl ChromaLocation {
+    /// Location of the chroma sample in luma samples, relative to the top-left luma sample
+    /// it is associated with
+    pub(crate) fn position(&self) -> (f32, f32) {
+        match self {
+            Self::Left => (0.0, 0.5),
+            Self::Center => (0.5, 0.5),
+            Self::TopLeft => (0.0, 0.0),
+            Self::Top => (0.5, 0.0),
+            Self::BottomLeft => (0.0, 1.0),
+            Self::Bottom => (0.5, 1.0),
+        }
+    }
+}
+
+/// Scan type of a frame, and the temporal order of its fields if interlaced
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FieldOrder {
+    #[default]
+    Progressive,
+    /// Interlaced, with the field of even lines captured first
+    TopFieldFirst,
+    /// Interlaced, with the field of odd lines captured first
+    BottomFieldFirst,
+}
+
+type Storage = dyn AsRef<[u8]> + Send + Sync;
+
+/// Bytes of a frame, shared rather than copied when cloned
+///
+/// Any storage exposing its bytes can be wrapped, e.g. `bytes::Bytes` or the mapped
+/// buffers of a decoder's pool, so frames reach the [`Program`] without copying. Only
+/// [`Format::NV12`] frames are copied, when they are converted to I420.
+///
+/// [`Program`]: crate::Program
+#[derive(Clone)]
+pub struct Data(Shared);
+
+/// Storage of [`Data`], where shared slices are kept as they are since they can't be
+/// turned into [`Storage`] without wrapping them again
+#[derive(Clone)]
+enum Shared {
+    Storage(Arc<Storage>),
+    Slice(Arc<[u8]>),
+}
+
+impl Data {
+    pub fn new(bytes: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Self(Shared::Storage(Arc::new(bytes)))
+    }
+
+    /// Whether both share the same storage
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self.as_ptr(), other.as_ptr())
+    }
+
+    /// Reference to the storage which doesn't keep it alive, e.g. in a decoder's pool
+    pub(crate) fn downgrade(&self) -> WeakData {
+        WeakData(match &self.0 {
+            Shared::Storage(storage) => Weak::Storage(Arc::downgrade(storage)),
+            Shared::Slice(slice) => Weak::Slice(Arc::downgrade(slice)),
+        })
+    }
+
+    /// Address of the storage, which differs from the address of the bytes for wrapped
+    /// storage
+    fn as_ptr(&self) -> *const u8 {
+        match &self.0 {
+            Shared::Storage(storage) => Arc::as_ptr(storage).cast(),
+            Shared::Slice(slice) => Arc::as_ptr(slice).cast(),
+        }
+    }
+}
+
+impl Deref for Data {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            Shared::Storage(storage) => storage.as_ref().as_ref(),
+            Shared::Slice(slice) => slice,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Data {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Default for Data {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl fmt::Debug for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Data").field("len", &self.len()).finish()
+    }
+}
+
+impl From<Vec<u8>> for Data {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<Arc<[u8]>> for Data {
+    fn from(bytes: Arc<[u8]>) -> Self {
+        Self(Shared::Slice(bytes))
+    }
+}
+
+impl From<Arc<Vec<u8>>> for Data {
+    fn from(bytes: Arc<Vec<u8>>) -> Self {
+        Self(Shared::Storage(bytes))
+    }
+}
+
+/// Weak reference to the storage of [`Data`]
+#[derive(Debug, Clone)]
+pub(crate) struct WeakData(Weak);
+
 #[derive(Debug, Clone)]
+enum Weak {
+    Storage(std::sync::Weak<Storage>),
+    Slice(std::sync::Weak<[u8]>),
+}
+
+impl WeakData {
+    /// Whether `data` is the storage referenced, which can't be mistaken for new storage
+    /// at the same address because the allocation is kept
+    pub fn is(&self, data: &Data) -> bool {
+        let ptr: *const u8 = match &self.0 {
+            Weak::Storage(storage) => storage.as_ptr().cast(),
+            Weak::Slice(slice) => slice.as_ptr().cast(),
+        };
+
+        std::ptr::addr_eq(ptr, data.as_ptr())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Yuv {
     pub format: Format,
-    pub data: Vec<u8>,
+    pub data: Data,
+    /// Identity of the frame, e.g. its sequence number, so that a frame with the same
+    /// sequence as the previous one isn't uploaded again
+    pub sequence: Option<u64>,
     pub dimensions: Size<u32>,
+    pub chroma_location: ChromaLocation,
+    pub field_order: FieldOrder,
+    pub matrix: MatrixCoefficients,
+    pub range: ColorRange,
+    pub transfer: TransferFunction,
+    pub primaries: ColorPrimaries,
+    /// Peak luminance of the mastering display in cd/m², for HDR transfer functions
+    pub peak_luminance: Option<f32>,
 }
 
 impl From<Yuv> for Renderable {
     fn from(
         Yuv {
-            mut data,
+            data,
             dimensions,
             format,
+            ..
         }: Yuv,
     ) -> Self {
         match format {
@@ -77,11 +259,13 @@ impl From<Yuv> for Renderable {
 
             Format::Y444 => Renderable::Y444 { data, dimensions },
 
+            // the interleaved chroma plane is split, which copies the frame
             Format::NV12 => {
                 let n = data.len() / 6;
-                let chroma = data.split_off(n * 4);
+                let (luma, chroma) = data.split_at(n * 4);
+                let mut data = luma.to_vec();
                 let (u, v): (Vec<_>, Vec<_>) =
-                    chroma.into_iter().enumerate().partition_map(|(i, b)| {
+                    chroma.iter().enumerate().partition_map(|(i, &b)| {
                         if i % 2 == 0 {
                             itertools::Either::Left(b)
                         } else {
@@ -92,13 +276,16 @@ impl From<Yuv> for Renderable {
                 data.extend(u);
                 data.extend(v);
 
-                Renderable::I420 { data, dimensions }
+                Renderable::I420 {
+                    data: data.into(),
+                    dimensions,
+                }
             }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Size<T = f32> {
     pub width: T,
     pub height: T,
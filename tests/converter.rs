@@ -0,0 +1,647 @@
+//! Compares frames converted by the shader against the CPU reference converter
+
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use iced::widget::shader::wgpu;
+use iced::{Border, Color, Point, Rectangle};
+use vanilla_iced::{
+    cpu, Buffering, ChromaKey, ColorPrimaries, Converter, Format, Lut, Mask, MatrixCoefficients,
+    PictureAdjustments, PostProcess, PrivacyRegion, Program, Redaction, Size, TransferFunction,
+    Yuv,
+};
+
+/// Largest difference of a channel between the shader and the CPU reference
+const TOLERANCE: u8 = 4;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// Device of any adapter, including software ones like llvmpipe
+///
+/// # Panics
+///
+/// Panics if there is no adapter at all, rather than letting the tests pass unchecked.
+fn device() -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::default();
+    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        force_fallback_adapter: false,
+        ..Default::default()
+    }))
+    .or_else(|| {
+        block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        }))
+    })
+    .expect("a wgpu adapter, e.g. a software one like llvmpipe or lavapipe");
+
+    block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).expect("device")
+}
+
+/// Smooth gradients across the frame, avoiding limited range clipping
+fn planes(width: u32, height: u32) -> [Vec<u8>; 3] {
+    let plane = |f: &dyn Fn(f32, f32) -> f32| {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x as f32 / width as f32, y as f32 / height as f32) as u8)
+            .collect()
+    };
+
+    [
+        plane(&|x, y| 64.0 + 96.0 * x + 48.0 * y),
+        plane(&|x, y| 128.0 + 24.0 * (x * 3.0 + y).sin()),
+        plane(&|x, y| 128.0 - 24.0 * (y * 4.0 - x).cos()),
+    ]
+}
+
+fn frame(format: Format) -> Yuv {
+    let [y, ..] = planes(WIDTH, HEIGHT);
+
+    let data = match format {
+        Format::Y444 => planes(WIDTH, HEIGHT).concat(),
+        Format::I420 => {
+            let [_, u, v] = planes(WIDTH / 2, HEIGHT / 2);
+            [y, u, v].concat()
+        }
+        Format::NV12 => {
+            let [_, u, v] = planes(WIDTH / 2, HEIGHT / 2);
+            let uv = u.into_iter().zip(v).flat_map(|(u, v)| [u, v]);
+            y.into_iter().chain(uv).collect()
+        }
+    };
+
+    Yuv {
+        format,
+        data: data.into(),
+        dimensions: Size {
+            width: WIDTH,
+            height: HEIGHT,
+        },
+        ..Default::default()
+    }
+}
+
+fn assert_matches_reference(program: Program, reference: &[u8]) {
+    let (device, queue) = device();
+
+    let snapshot = Converter::new(&device, &queue)
+        .rgba(&program)
+        .expect("converted frame");
+
+    assert_eq!(snapshot.dimensions.width, WIDTH);
+    assert_eq!(snapshot.dimensions.height, HEIGHT);
+    assert_close(&snapshot.rgba, reference);
+}
+
+fn pixel(rgba: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let i = ((y * WIDTH + x) * 4) as usize;
+    [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+}
+
+fn assert_close(rgba: &[u8], reference: &[u8]) {
+    assert_eq!(rgba.len(), reference.len());
+
+    let (i, difference) = rgba
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| a.abs_diff(*b))
+        .enumerate()
+        .max_by_key(|(_, difference)| *difference)
+        .expect("pixels");
+
+    assert!(
+        difference <= TOLERANCE,
+        "pixel {} differs by {difference}: {:?} != {:?}",
+        i / 4,
+        &rgba[i / 4 * 4..i / 4 * 4 + 4],
+        &reference[i / 4 * 4..i / 4 * 4 + 4],
+    );
+}
+
+#[test]
+fn i420() {
+    let yuv = frame(Format::I420);
+    assert_matches_reference(Program::new(yuv.clone()), &cpu::to_rgba(&yuv).unwrap());
+}
+
+#[test]
+fn nv12() {
+    let yuv = frame(Format::NV12);
+    assert_matches_reference(Program::new(yuv.clone()), &cpu::to_rgba(&yuv).unwrap());
+}
+
+#[test]
+fn y444() {
+    let yuv = frame(Format::Y444);
+    assert_matches_reference(Program::new(yuv.clone()), &cpu::to_rgba(&yuv).unwrap());
+}
+
+#[test]
+fn hdr() {
+    let yuv = Yuv {
+        transfer: TransferFunction::Pq,
+        primaries: ColorPrimaries::Bt2020,
+        peak_luminance: Some(1000.0),
+        // sampled at texel centers, so that filtering precision isn't amplified by PQ
+        ..frame(Format::Y444)
+    };
+
+    assert_matches_reference(Program::new(yuv.clone()), &cpu::to_rgba(&yuv).unwrap());
+}
+
+#[test]
+fn ictcp() {
+    let yuv = Yuv {
+        matrix: MatrixCoefficients::ICtCp,
+        transfer: TransferFunction::Pq,
+        primaries: ColorPrimaries::Bt2020,
+        peak_luminance: Some(1000.0),
+        ..frame(Format::Y444)
+    };
+
+    assert_matches_reference(Program::new(yuv.clone()), &cpu::to_rgba(&yuv).unwrap());
+}
+
+#[test]
+fn adjustments() {
+    let yuv = frame(Format::Y444);
+    let adjustments = PictureAdjustments {
+        brightness: 0.05,
+        contrast: 1.2,
+        saturation: 0.8,
+        hue: 0.3,
+        gamma: 1.1,
+    };
+
+    let program = Program::new(yuv.clone()).adjustments(adjustments);
+    let reference = program.to_rgba().expect("frame");
+
+    assert_ne!(reference, cpu::to_rgba(&yuv).unwrap());
+    assert_matches_reference(program, &reference);
+}
+
+#[test]
+fn staged_uploads() {
+    let (device, queue) = device();
+
+    let first = frame(Format::I420);
+    let mut program = Program::new(first.clone()).buffering(Buffering::Triple);
+    let mut converter = Converter::new(&device, &queue);
+
+    // each frame goes to another texture, and is read back once its copy completed
+    for yuv in [first.clone(), frame(Format::Y444), first] {
+        program.update_frame(yuv.clone());
+
+        let snapshot = converter.rgba(&program).expect("converted frame");
+        let difference = snapshot
+            .rgba
+            .iter()
+            .zip(cpu::to_rgba(&yuv).unwrap())
+            .map(|(a, b)| a.abs_diff(b))
+            .max();
+
+        assert!(difference <= Some(TOLERANCE));
+    }
+}
+
+#[test]
+fn dirty_regions() {
+    let (device, queue) = device();
+
+    let first = frame(Format::I420);
+    let mut program = Program::new(first.clone());
+    let mut converter = Converter::new(&device, &queue);
+    converter.rgba(&program).expect("converted frame");
+
+    // both corners change, but only the top left one is marked as dirty
+    let corners = |luma: u8| {
+        let mut data = first.data.to_vec();
+        for row in [0, 1, HEIGHT as usize - 2, HEIGHT as usize - 1] {
+            let start = row * WIDTH as usize;
+            data[start..start + 2].fill(luma);
+            data[start + WIDTH as usize - 2..start + WIDTH as usize].fill(luma);
+        }
+        data
+    };
+
+    let mut top_left = first.data.to_vec();
+    top_left[..2].fill(235);
+    top_left[WIDTH as usize..WIDTH as usize + 2].fill(235);
+
+    program.update_regions(
+        Yuv {
+            data: corners(235).into(),
+            ..first.clone()
+        },
+        &[Rectangle {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 2,
+        }],
+    );
+
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    let reference = cpu::to_rgba(&Yuv {
+        data: top_left.into(),
+        ..first
+    })
+    .unwrap();
+    let difference = snapshot
+        .rgba
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| a.abs_diff(b))
+        .max();
+
+    assert!(difference <= Some(TOLERANCE));
+}
+
+#[test]
+fn post_process() {
+    let yuv = frame(Format::Y444);
+    let post = PostProcess::new(
+        "fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+            return vec4<f32>(uniforms.post[0].rgb - rgb.rgb, rgb.a);
+        }",
+    )
+    .expect("valid hook");
+
+    let mut program = Program::new(yuv.clone()).post_process(post);
+    program.update_post_uniforms([[1.0; 4], [0.0; 4], [0.0; 4], [0.0; 4]]);
+
+    let inverted: Vec<u8> = cpu::to_rgba(&yuv)
+        .unwrap()
+        .chunks(4)
+        .flat_map(|pixel| [255 - pixel[0], 255 - pixel[1], 255 - pixel[2], pixel[3]])
+        .collect();
+
+    assert_matches_reference(program, &inverted);
+}
+
+#[test]
+fn reused_converter() {
+    let (device, queue) = device();
+    let mut converter = Converter::new(&device, &queue);
+
+    let yuv = frame(Format::I420);
+    let program = Program::new(yuv.clone());
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &cpu::to_rgba(&yuv).unwrap());
+
+    // another format, hook and number of frames with the same dimensions
+    let yuv = frame(Format::Y444);
+    let post = PostProcess::new(
+        "fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+            return vec4<f32>(1.0 - rgb.rgb, rgb.a);
+        }",
+    )
+    .expect("valid hook");
+    let program = Program::new(yuv.clone())
+        .post_process(post)
+        .buffering(Buffering::Triple);
+
+    let inverted: Vec<u8> = cpu::to_rgba(&yuv)
+        .unwrap()
+        .chunks(4)
+        .flat_map(|pixel| [255 - pixel[0], 255 - pixel[1], 255 - pixel[2], pixel[3]])
+        .collect();
+
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &inverted);
+}
+
+#[test]
+fn changed_post_process() {
+    let (device, queue) = device();
+    let mut converter = Converter::new(&device, &queue);
+
+    let yuv = frame(Format::Y444);
+    let reference = cpu::to_rgba(&yuv).unwrap();
+    let post = PostProcess::new(
+        "fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 0.0, rgb.a);
+        }",
+    )
+    .expect("valid hook");
+
+    let mut program = Program::new(yuv.clone()).post_process(post);
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert!(snapshot.rgba.chunks(4).all(|pixel| pixel[..3] == [0, 0, 0]));
+
+    program.update_post_process(PostProcess::default());
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &reference);
+}
+
+#[test]
+fn chroma_key() {
+    let yuv = frame(Format::Y444);
+
+    // the chroma of the frame is far from green, so nothing is keyed out
+    let green = ChromaKey {
+        similarity: 0.1,
+        ..ChromaKey::default()
+    };
+    let program = Program::new(yuv.clone()).chroma_key(green);
+    assert_matches_reference(program, &cpu::to_rgba(&yuv).unwrap());
+
+    let (device, queue) = device();
+
+    let gray = ChromaKey {
+        color: Color::from_rgb(0.5, 0.5, 0.5),
+        ..ChromaKey::default()
+    };
+
+    let snapshot = Converter::new(&device, &queue)
+        .rgba(&Program::new(yuv).chroma_key(gray))
+        .expect("converted frame");
+
+    // snapshots are drawn over black
+    assert!(snapshot.rgba.chunks(4).all(|pixel| pixel == [0, 0, 0, 255]));
+}
+
+#[test]
+fn shape() {
+    let (device, queue) = device();
+    let mut converter = Converter::new(&device, &queue);
+
+    let yuv = frame(Format::Y444);
+    let reference = cpu::to_rgba(&yuv).unwrap();
+    let rgb = |rgba: [u8; 4]| [rgba[0], rgba[1], rgba[2]];
+    let assert_shown = |rgba: &[u8], x, y| {
+        let (shown, expected) = (pixel(rgba, x, y), pixel(&reference, x, y));
+        let difference = (0..3).map(|c| shown[c].abs_diff(expected[c])).max();
+
+        assert!(
+            difference <= Some(TOLERANCE),
+            "({x}, {y}): {shown:?} != {expected:?}"
+        );
+    };
+
+    // the corners are cut outside of the radius, and the border is drawn along the edges
+    let program = Program::new(yuv.clone()).border(Border {
+        color: Color::from_rgb(1.0, 0.0, 0.0),
+        width: 2.0,
+        radius: 16.0.into(),
+    });
+    let snapshot = converter.rgba(&program).expect("converted frame");
+
+    assert_eq!(rgb(pixel(&snapshot.rgba, 0, 0)), [0, 0, 0]);
+    assert_eq!(rgb(pixel(&snapshot.rgba, WIDTH - 2, HEIGHT - 2)), [0, 0, 0]);
+    assert_eq!(rgb(pixel(&snapshot.rgba, WIDTH / 2, 0)), [255, 0, 0]);
+    assert_eq!(rgb(pixel(&snapshot.rgba, 1, HEIGHT / 2)), [255, 0, 0]);
+    assert_shown(&snapshot.rgba, WIDTH / 2, HEIGHT / 2);
+    assert_shown(&snapshot.rgba, 8, 8);
+
+    // the left half of the mask is transparent
+    let alpha: Vec<u8> = (0..WIDTH * HEIGHT)
+        .map(|i| if i % WIDTH < WIDTH / 2 { 0 } else { 255 })
+        .collect();
+    let program = Program::new(yuv.clone()).mask(Mask {
+        alpha: alpha.into(),
+        dimensions: Size {
+            width: WIDTH,
+            height: HEIGHT,
+        },
+    });
+    let snapshot = converter.rgba(&program).expect("converted frame");
+
+    for y in 0..HEIGHT {
+        assert_eq!(rgb(pixel(&snapshot.rgba, WIDTH / 4, y)), [0, 0, 0]);
+        assert_shown(&snapshot.rgba, WIDTH * 3 / 4, y);
+    }
+}
+
+#[test]
+fn cross_fade() {
+    let (device, queue) = device();
+    let mut converter = Converter::new(&device, &queue);
+
+    let yuv = frame(Format::Y444);
+    let reference = cpu::to_rgba(&yuv).unwrap();
+    let halved: Vec<u8> = reference
+        .chunks(4)
+        .flat_map(|pixel| {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| (c as f32 / 2.0).round() as u8);
+            [r, g, b, pixel[3]]
+        })
+        .collect();
+    let gray = Yuv {
+        data: vec![128; (WIDTH * HEIGHT * 3) as usize].into(),
+        ..yuv.clone()
+    };
+
+    // half transparent over black
+    let mut program = Program::new(yuv.clone())
+        .cross_fade(Duration::from_secs(3600))
+        .opacity(0.5);
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &halved);
+
+    // the fade to the next frame has barely started
+    program.update_frame(gray.clone());
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &halved);
+
+    // without the fade, new frames are shown at once
+    program.update_cross_fade(None);
+    program.update_opacity(1.0);
+    program.update_frame(Yuv {
+        data: yuv.data.to_vec().into(),
+        ..yuv
+    });
+    let snapshot = converter.rgba(&program).expect("converted frame");
+    assert_close(&snapshot.rgba, &reference);
+}
+
+#[test]
+fn privacy_regions() {
+    let (device, queue) = device();
+
+    let yuv = frame(Format::Y444);
+    let red = Color::from_rgb(1.0, 0.0, 0.0);
+    let program = Program::new(yuv.clone()).privacy_regions(&[
+        PrivacyRegion::rectangle(
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 0.5,
+                height: 1.0,
+            },
+            Redaction::Solid(red),
+        ),
+        PrivacyRegion::polygon(
+            [
+                Point::new(0.5, 0.0),
+                Point::new(1.0, 0.0),
+                Point::new(1.0, 0.5),
+                Point::new(0.5, 0.5),
+            ],
+            Redaction::Pixelate(8.0),
+        ),
+    ]);
+
+    let snapshot = Converter::new(&device, &queue)
+        .rgba(&program)
+        .expect("converted frame");
+    let reference = cpu::to_rgba(&yuv).unwrap();
+
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let shown = pixel(&snapshot.rgba, x, y);
+
+            if x < WIDTH / 2 {
+                assert_eq!(shown, [255, 0, 0, 255], "({x}, {y})");
+            } else if y < HEIGHT / 2 {
+                let block = pixel(&snapshot.rgba, x / 8 * 8, y / 8 * 8);
+                assert_eq!(shown, block, "({x}, {y})");
+            } else {
+                let expected = pixel(&reference, x, y);
+                let difference = shown.iter().zip(expected).map(|(a, b)| a.abs_diff(b));
+                assert!(difference.max() <= Some(TOLERANCE), "({x}, {y})");
+            }
+        }
+    }
+}
+
+#[test]
+fn privacy_regions_on_the_cpu() {
+    let (device, queue) = device();
+
+    let yuv = frame(Format::Y444);
+    let program = Program::new(yuv).privacy_regions(&[
+        PrivacyRegion::rectangle(
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 0.5,
+                height: 0.5,
+            },
+            Redaction::Solid(Color::from_rgb(0.0, 0.0, 1.0)),
+        ),
+        PrivacyRegion::polygon(
+            [
+                Point::new(0.5, 0.5),
+                Point::new(1.0, 0.5),
+                Point::new(1.0, 1.0),
+            ],
+            Redaction::Pixelate(8.0),
+        ),
+    ]);
+
+    // the frame drawn by renderers without shaders hides the regions like the shader
+    let snapshot = Converter::new(&device, &queue)
+        .rgba(&program)
+        .expect("converted frame");
+    let converted = program.to_rgba().expect("frame");
+
+    assert_eq!(pixel(&converted, 0, 0), [0, 0, 255, 255]);
+    assert_close(&converted, &snapshot.rgba);
+}
+
+/// Sum of the differences between horizontally adjacent pixels
+fn high_frequency_energy(rgba: &[u8]) -> u32 {
+    rgba.chunks(WIDTH as usize * 4)
+        .flat_map(|row| {
+            row.windows(8)
+                .step_by(4)
+                .map(|pair| pair[0].abs_diff(pair[4]) as u32)
+        })
+        .sum()
+}
+
+#[test]
+fn blurred_regions() {
+    let (device, queue) = device();
+
+    // columns alternating between black and white
+    let luma = (0..WIDTH * HEIGHT).map(|i| if i % 2 == 0 { 16 } else { 235 });
+    let yuv = Yuv {
+        data: luma
+            .chain(std::iter::repeat_n(128, (WIDTH * HEIGHT * 2) as usize))
+            .collect::<Vec<u8>>()
+            .into(),
+        ..frame(Format::Y444)
+    };
+    let original = high_frequency_energy(&cpu::to_rgba(&yuv).unwrap());
+
+    let program = Program::new(yuv).privacy_regions(&[PrivacyRegion::rectangle(
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        },
+        Redaction::Blur(6.0),
+    )]);
+
+    let snapshot = Converter::new(&device, &queue)
+        .rgba(&program)
+        .expect("converted frame");
+    let converted = program.to_rgba().expect("frame");
+
+    for blurred in [&snapshot.rgba, &converted] {
+        let energy = high_frequency_energy(blurred);
+        assert!(energy * 20 < original, "{energy} of {original}");
+    }
+}
+
+#[test]
+fn shaper_wider_than_textures() {
+    let yuv = frame(Format::Y444);
+
+    // wider than the default limit of 2D textures, so the table wraps into rows
+    let size = 16384;
+    let entries: String = (0..size)
+        .map(|i| {
+            let value = 1.0 - i as f32 / (size - 1) as f32;
+            format!("{value} {value} {value}\n")
+        })
+        .collect();
+    let lut = Lut::parse(&format!("LUT_1D_SIZE {size}\n{entries}")).expect("valid LUT");
+
+    let inverted: Vec<u8> = cpu::to_rgba(&yuv)
+        .unwrap()
+        .chunks(4)
+        .flat_map(|pixel| [255 - pixel[0], 255 - pixel[1], 255 - pixel[2], pixel[3]])
+        .collect();
+
+    assert_matches_reference(Program::new(yuv).lut(lut, 1.0), &inverted);
+}
+
+#[test]
+fn invalid_post_process() {
+    let error = PostProcess::new(
+        "fn post(rgb: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+            return rgb * undefined;
+        }",
+    )
+    .expect_err("invalid hook");
+
+    assert!(error.to_string().contains("post.wgsl:2"), "{error}");
+}